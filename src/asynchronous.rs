@@ -0,0 +1,305 @@
+use std::{
+
+    future::{Future},
+    ops::{Range},
+    net::{IpAddr},
+    pin::{Pin},
+
+    task::{
+
+        Context,
+        Poll,
+    },
+
+    time::{
+
+        Duration,
+        Instant,
+    },
+};
+
+use tokio::{
+
+    time::{
+
+        timeout_at,
+        sleep_until,
+        Sleep,
+    },
+};
+
+use futures_core::{Stream};
+
+use super::{
+
+    requests::{EkkoRequests},
+    packets::{payload},
+    error::{EkkoError},
+
+    sender::{
+
+        EkkoSettings,
+        Ekko,
+    },
+
+    socket::{
+
+        EkkoProbe,
+        EkkoMode,
+    },
+
+    responses::{EkkoResponse},
+};
+
+/// Asynchronous counterpart to [`Ekko`], responses are awaited instead of
+/// blocking the thread.
+///
+/// The socket is not registered with the tokio reactor, it is read by the
+/// same background thread as for [`Ekko`], which wakes tasks awaiting the
+/// responses it reads. The runtime is only needed for timeouts.
+///
+/// ## Example
+/// ```rust,no_run
+/// use ekko::{
+///
+///     EkkoError,
+///     AsyncEkko,
+/// };
+///
+/// #[tokio::main]
+/// async fn main() -> Result<(), EkkoError> {
+///     let sender = AsyncEkko::with_target([8, 8, 8, 8])?;
+///
+///     for ekko in sender.send_range(1..32).await? {
+///         println!("{:?}", ekko)
+///     }
+///
+///     Ok(())
+/// }
+/// ```
+#[derive(Clone)]
+pub struct AsyncEkko {
+
+    sender: Ekko,
+}
+
+impl AsyncEkko {
+
+    /// Build a sender with given target address, uses a raw socket when
+    /// permitted and falls back to an unprivileged datagram socket otherwise.
+    pub fn with_target<T: Into<IpAddr>>(target: T) -> Result<AsyncEkko, EkkoError> {
+        AsyncEkko::with_sender(Ekko::with_target(target)?)
+    }
+
+    /// Build a sender with given target address and socket mode.
+    pub fn with_target_and_mode<T: Into<IpAddr>>(target: T, mode: EkkoMode) -> Result<AsyncEkko, EkkoError> {
+        AsyncEkko::with_sender(Ekko::with_target_and_mode(target, mode)?)
+    }
+
+    /// Build a sender with given target address and kind of request.
+    pub fn with_target_and_probe<T: Into<IpAddr>>(target: T, probe: EkkoProbe) -> Result<AsyncEkko, EkkoError> {
+        AsyncEkko::with_sender(Ekko::with_target_and_probe(target, probe)?)
+    }
+
+    /// Build a sender from an existing one, both share the same socket.
+    pub fn with_sender(sender: Ekko) -> Result<AsyncEkko, EkkoError> {
+        Ok(AsyncEkko {
+
+            sender,
+        })
+    }
+
+    /// Socket mode in use by this sender.
+    pub fn mode(&self) -> EkkoMode {
+        self.sender.mode()
+    }
+
+    /// Have responses to this sender carry the round trip as seen by the
+    /// kernel, see [`Ekko::set_timestamps`].
+    pub fn set_timestamps(&self, enable: bool) -> Result<(), EkkoError> {
+        self.sender.set_timestamps(enable)
+    }
+
+    /// Send an echo request with default settings.
+    pub async fn send(&self, hops: u32) -> Result<EkkoResponse, EkkoError> {
+        self.send_with_settings(hops, Default::default()).await
+    }
+
+    /// Send an echo request with user defined settings.
+    pub async fn send_with_settings(&self, hops: u32, EkkoSettings {
+        timeout, identifier, sequence, flow, length, pattern, tos
+    }: EkkoSettings) -> Result<EkkoResponse, EkkoError> {
+
+        let sender = &(self.sender);
+        let payload = payload(sender.target(), flow, {
+            (length, &(pattern))
+        })?;
+
+        sender.inner_reserve(1, payload.len())?;
+
+        let timepoint = Instant::now();
+        let mut requests = sender.inner_requests(1);
+
+        let pkt = sender.inner_send(hops, tos, {
+            (identifier, sequence)
+        }, flow, &(payload))?;
+
+        requests.push(timepoint, sender.target(), pkt, hops);
+        self.inner_wait(&mut requests, timepoint + timeout).await?;
+
+        Ok(requests.finish().remove(0))
+    }
+
+    /// Send echo requests for all hops in range with default settings.
+    pub async fn send_range(&self, hops: Range<u32>) -> Result<Vec<EkkoResponse>, EkkoError> {
+        self.send_range_with_settings(hops, Default::default()).await
+    }
+
+    /// Send echo requests for all hops in range with user defined settings.
+    pub async fn send_range_with_settings(&self, hops: Range<u32>, EkkoSettings {
+        timeout, identifier, sequence, flow, length, pattern, tos
+    }: EkkoSettings) -> Result<Vec<EkkoResponse>, EkkoError> {
+
+        let sender = &(self.sender);
+        let payload = payload(sender.target(), flow, {
+            (length, &(pattern))
+        })?;
+
+        sender.inner_reserve(hops.len(), payload.len())?;
+
+        let timepoint = Instant::now();
+        let mut requests = sender.inner_send_range(hops, tos, {
+            (identifier, sequence)
+        }, flow, &(payload))?;
+
+        self.inner_wait(&mut requests, timepoint + timeout).await?;
+
+        Ok(requests.finish())
+    }
+
+    /// Send echo requests for all hops in range with default settings,
+    /// responses are yielded as they arrive.
+    pub fn stream_range(&self, hops: Range<u32>) -> Result<AsyncEkkoStream, EkkoError> {
+        self.stream_range_with_settings(hops, Default::default())
+    }
+
+    /// Send echo requests for all hops in range with user defined settings,
+    /// responses are yielded as they arrive.
+    pub fn stream_range_with_settings(&self, hops: Range<u32>, EkkoSettings {
+        timeout, identifier, sequence, flow, length, pattern, tos
+    }: EkkoSettings) -> Result<AsyncEkkoStream, EkkoError> {
+
+        let sender = &(self.sender);
+        let payload = payload(sender.target(), flow, {
+            (length, &(pattern))
+        })?;
+
+        sender.inner_reserve(hops.len(), payload.len())?;
+
+        let requests = sender.inner_send_range(hops, tos, {
+            (identifier, sequence)
+        }, flow, &(payload))?;
+
+        Ok(AsyncEkkoStream {
+
+            sleep: None,
+            requests,
+            timeout,
+        })
+    }
+
+    async fn inner_wait(&self, requests: &mut EkkoRequests, deadline: Instant) -> Result<(), EkkoError> {
+        let result = timeout_at(deadline.into(), async {
+            while !(requests.is_complete()) {
+                requests.wait_async().await?;
+            }
+
+            Ok(())
+        }).await;
+
+        match result {
+            Ok(result) => result,
+            Err(_) => Ok(()),
+        }
+    }
+}
+
+/// Asynchronous counterpart to [`EkkoStream`](crate::EkkoStream), responses
+/// to requests already sent are yielded as soon as they are matched. A
+/// [`Stream`], so it works with the combinators of any stream library.
+///
+/// ## Example
+/// ```rust,no_run
+/// use ekko::{
+///
+///     EkkoError,
+///     AsyncEkko,
+/// };
+///
+/// #[tokio::main]
+/// async fn main() -> Result<(), EkkoError> {
+///     let sender = AsyncEkko::with_target([8, 8, 8, 8])?;
+///     let mut stream = sender.stream_range(1..32)?;
+///
+///     while let Some(ekko) = stream.next().await {
+///         println!("{:?}", ekko?)
+///     }
+///
+///     Ok(())
+/// }
+/// ```
+pub struct AsyncEkkoStream {
+
+    /// Wakes the stream once the oldest outstanding request times out, made
+    /// when first polled so streams can be built outside of a runtime.
+    sleep: Option<Pin<Box<Sleep>>>,
+    requests: EkkoRequests,
+    timeout: Duration,
+}
+
+impl AsyncEkkoStream {
+
+    /// Wait for the next response, requests going unanswered for the timeout
+    /// are yielded as lacking. None once every request was yielded.
+    pub async fn next(&mut self) -> Option<Result<EkkoResponse, EkkoError>> {
+        std::future::poll_fn(|cx| Pin::new(&mut *self).poll_next(cx)).await
+    }
+}
+
+impl Stream for AsyncEkkoStream {
+    type Item = Result<EkkoResponse, EkkoError>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Result<EkkoResponse, EkkoError>>> {
+        let stream = self.get_mut();
+
+        loop {
+
+            if let Some(response) = stream.requests.next(stream.timeout) {
+                return Poll::Ready(Some(Ok(response)))
+            }
+
+            let deadline = match stream.requests.deadline(stream.timeout) {
+                Some(deadline) => deadline.into(),
+                None => return Poll::Ready(None),
+            };
+
+            match stream.requests.poll_wait(cx) {
+                Poll::Ready(Err(e)) => return Poll::Ready(Some(Err(e))),
+                Poll::Ready(Ok(())) => continue,
+                Poll::Pending => (),
+            }
+
+            let sleep = stream.sleep.get_or_insert_with(|| {
+                Box::pin(sleep_until(deadline))
+            });
+
+            if sleep.deadline() != deadline {
+                sleep.as_mut().reset(deadline);
+            }
+
+            if sleep.as_mut().poll(cx).is_pending() {
+                return Poll::Pending
+            }
+        }
+    }
+}
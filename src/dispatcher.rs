@@ -0,0 +1,453 @@
+use std::{
+
+    collections::{
+
+        VecDeque,
+        HashMap,
+    },
+
+    sync::{
+
+        atomic::{
+
+            AtomicBool,
+            Ordering,
+        },
+
+        Condvar,
+        Mutex,
+        Weak,
+        Arc,
+    },
+
+    thread::{JoinHandle},
+    task::{Waker},
+
+    time::{
+
+        Duration,
+        Instant,
+    },
+
+    net::{
+
+        SocketAddr,
+        IpAddr,
+    },
+};
+
+use super::{
+
+    requests::{EkkoRequests},
+    packets::{EkkoPacket},
+    error::{EkkoError},
+
+    socket::{
+
+        RESPONSE_OCTETS,
+        EkkoReceived,
+        EkkoSocket,
+        EkkoProbe,
+        EkkoMode,
+    },
+};
+
+/// Time packets are kept after routing, responses may arrive before the
+/// request they respond to is registered.
+const RECENT_TIMEOUT: Duration = Duration::from_millis(1000);
+/// Octets of packets kept after routing at most.
+const RECENT_OCTETS: usize = 1 << 20;
+
+/// Dispatchers shared by senders with the same socket options.
+#[allow(clippy::type_complexity)]
+static SHARED: Mutex<Vec<((SocketAddr, Option<String>, Option<EkkoMode>, EkkoProbe), Weak<EkkoDispatcher>)>> = Mutex::new(Vec::new());
+
+/// What a packet is routed by.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub(crate) enum EkkoRoute {
+    /// Target, identifier and sequence of the request responded to.
+    Request((IpAddr, u16, u16)),
+    /// Key of the request a send timestamp is for.
+    Transmitted(u32),
+}
+
+/// Packet read by the dispatcher, owned so it can be handed to whoever waits
+/// for it.
+#[derive(Clone)]
+pub(crate) enum EkkoOwned {
+    Response(SocketAddr, fn(&[u8]) -> EkkoPacket<'_>, Vec<u8>, Option<Duration>, Option<u8>, bool),
+    Transmitted(u32, Duration),
+}
+
+impl EkkoOwned {
+
+    fn new(received: EkkoReceived) -> Result<Option<(EkkoRoute, EkkoOwned)>, EkkoError> {
+        match received {
+
+            EkkoReceived::Transmitted(key, timestamp) => Ok(Some((EkkoRoute::Transmitted(key), {
+                EkkoOwned::Transmitted(key, timestamp)
+            }))),
+
+            EkkoReceived::Response(address, packet, timestamp, ttl, rebuilt) => {
+                let key = match EkkoRequests::key(address.ip(), &(packet))? {
+                    Some(key) => key,
+                    None => return Ok(None),
+                };
+
+                let kind: fn(&[u8]) -> EkkoPacket<'_> = match packet {
+                    EkkoPacket::V4(_) => |packet| EkkoPacket::V4(packet),
+                    EkkoPacket::V6(_) => |packet| EkkoPacket::V6(packet),
+                    EkkoPacket::Udp(_) => |packet| EkkoPacket::Udp(packet),
+                    EkkoPacket::Tcp(_) => |packet| EkkoPacket::Tcp(packet),
+                };
+
+                Ok(Some((EkkoRoute::Request(key), {
+                    EkkoOwned::Response(address, kind, packet.as_slice().to_vec(), timestamp, ttl, rebuilt)
+                })))
+            },
+        }
+    }
+
+    pub(crate) fn received(&self) -> EkkoReceived<'_> {
+        match self {
+            EkkoOwned::Response(address, kind, packet, timestamp, ttl, rebuilt) => {
+                EkkoReceived::Response(*address, kind(packet), *timestamp, *ttl, *rebuilt)
+            },
+
+            EkkoOwned::Transmitted(key, timestamp) => EkkoReceived::Transmitted(*key, *timestamp),
+        }
+    }
+
+    fn octets(&self) -> usize {
+        match self {
+            EkkoOwned::Response(_, _, packet, ..) => packet.len(),
+            EkkoOwned::Transmitted(..) => 0,
+        }
+    }
+}
+
+/// Packets routed to a set of requests, waited for by a thread or a task.
+#[derive(Default)]
+pub(crate) struct EkkoInbox {
+    mail: Mutex<(VecDeque<EkkoOwned>, Option<Waker>)>,
+    ready: Condvar,
+}
+
+impl EkkoInbox {
+
+    fn deliver(&self, owned: EkkoOwned) {
+        let mut mail = self.mail.lock().unwrap_or_else(|e| e.into_inner());
+        mail.0.push_back(owned);
+
+        if let Some(waker) = mail.1.take() {
+            waker.wake();
+        }
+
+        self.ready.notify_all();
+    }
+
+    /// Packets delivered so far, waits for some until deadline.
+    pub(crate) fn wait(&self, deadline: Instant) -> VecDeque<EkkoOwned> {
+        let mut mail = self.mail.lock().unwrap_or_else(|e| e.into_inner());
+
+        while mail.0.is_empty() {
+            let timeout = match deadline.checked_duration_since(Instant::now()) {
+                Some(timeout) if timeout > Duration::from_secs(0) => timeout,
+                _ => break,
+            };
+
+            mail = self.ready.wait_timeout(mail, timeout).map(|(mail, _)| mail)
+                .unwrap_or_else(|e| e.into_inner().0);
+        }
+
+        std::mem::take(&mut mail.0)
+    }
+
+    /// Packets delivered so far, pending until there are some.
+    #[cfg(all(feature = "async", unix))]
+    pub(crate) fn poll(&self, cx: &mut std::task::Context<'_>) -> std::task::Poll<VecDeque<EkkoOwned>> {
+        let mut mail = self.mail.lock().unwrap_or_else(|e| e.into_inner());
+
+        match mail.0.is_empty() {
+
+            true => {
+                mail.1 = Some(cx.waker().clone());
+                std::task::Poll::Pending
+            },
+
+            false => std::task::Poll::Ready({
+                std::mem::take(&mut mail.0)
+            }),
+        }
+    }
+}
+
+#[derive(Default)]
+struct EkkoRoutes {
+    routes: HashMap<EkkoRoute, Vec<Weak<EkkoInbox>>>,
+    recent: VecDeque<(Instant, EkkoRoute, EkkoOwned)>,
+    octets: usize,
+}
+
+impl EkkoRoutes {
+
+    fn route(&mut self, route: EkkoRoute, owned: EkkoOwned) {
+        // Requests alike on the wire all get the packet.
+        if let Some(inboxes) = self.routes.get(&(route)) {
+            for inbox in inboxes.iter().filter_map(Weak::upgrade) {
+                inbox.deliver(owned.clone());
+            }
+        }
+
+        self.octets += owned.octets();
+        self.recent.push_back((Instant::now(), route, owned));
+
+        self.expire();
+    }
+
+    /// Forget packets kept for too long.
+    fn expire(&mut self) {
+        while let Some((timepoint, _, owned)) = self.recent.front() {
+            if timepoint.elapsed() < RECENT_TIMEOUT && self.octets <= RECENT_OCTETS {
+                break
+            }
+
+            self.octets -= owned.octets();
+            self.recent.pop_front();
+        }
+    }
+
+    /// Forget packets kept for too long, along with routes to inboxes that
+    /// are gone.
+    fn prune(&mut self) {
+        self.expire();
+        self.routes.retain(|_, inboxes| {
+            inboxes.retain(|inbox| inbox.strong_count() > 0);
+            !(inboxes.is_empty())
+        });
+    }
+}
+
+/// Reads a socket on a thread of its own and routes each packet to the
+/// requests waiting for it, so any number of senders share a socket without
+/// reading each others packets. The thread is stopped and joined once the
+/// last sender lets go of the dispatcher.
+pub(crate) struct EkkoDispatcher {
+    socket: Arc<EkkoSocket>,
+    routes: Arc<Mutex<EkkoRoutes>>,
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl EkkoDispatcher {
+
+    /// Dispatcher shared with every other sender using the same options,
+    /// opens a socket when there is none. Datagram sockets are never shared,
+    /// the kernel has every request on one carry its port as identifier, so
+    /// senders alike would send alike requests and take each others
+    /// responses.
+    pub(crate) fn shared(source: SocketAddr, device: Option<&str>, mode: Option<EkkoMode>, probe: EkkoProbe) -> Result<Arc<EkkoDispatcher>, EkkoError> {
+        let mut shared = SHARED.lock().unwrap_or_else(|e| e.into_inner());
+
+        shared.retain(|(_, dispatcher)| dispatcher.strong_count() > 0);
+
+        let options = (source, device.map(str::to_owned), mode, probe);
+        let found = shared.iter().find(|(shared, _)| *shared == options)
+            .and_then(|(_, dispatcher)| dispatcher.upgrade());
+
+        if let Some(dispatcher) = found {
+            return Ok(dispatcher)
+        }
+
+        let dispatcher = EkkoDispatcher::new(EkkoSocket::with_device(source, device, mode, probe)?)?;
+
+        if let EkkoMode::Raw = dispatcher.socket().mode() {
+            shared.push((options, Arc::downgrade(&(dispatcher))));
+        }
+
+        Ok(dispatcher)
+    }
+
+    /// Dispatcher of a socket of its own.
+    pub(crate) fn new(socket: EkkoSocket) -> Result<Arc<EkkoDispatcher>, EkkoError> {
+        let mut dispatcher = EkkoDispatcher {
+
+            socket: Arc::new(socket),
+            routes: Default::default(),
+            stop: Default::default(),
+            thread: None,
+        };
+
+        let (socket, routes, stop) = (dispatcher.socket.clone(), dispatcher.routes.clone(), dispatcher.stop.clone());
+
+        dispatcher.thread = Some(std::thread::Builder::new().name("ekko".to_owned()).spawn(move || {
+            EkkoDispatcher::run(&(socket), &(routes), &(stop))
+        }).map_err(|e| {
+            EkkoError::SocketDispatch(e.to_string())
+        })?);
+
+        Ok(Arc::new(dispatcher))
+    }
+
+    fn run(socket: &EkkoSocket, routes: &Mutex<EkkoRoutes>, stop: &AtomicBool) {
+        let mut buf: Vec<u8> = {
+            vec![0; RESPONSE_OCTETS]
+        };
+
+        while !(stop.load(Ordering::Relaxed)) {
+
+            socket.wait(Duration::from_millis(100));
+
+            while !(stop.load(Ordering::Relaxed)) {
+
+                // Packets failing to parse are of no use to anyone.
+                let (route, owned) = match socket.recv(&mut buf).map(|received| received.map(EkkoOwned::new)) {
+                    Ok(Some(Ok(Some(routed)))) => routed,
+                    Ok(Some(_)) | Err(_) => continue,
+                    Ok(None) => break,
+                };
+
+                routes.lock().unwrap_or_else(|e| e.into_inner())
+                    .route(route, owned);
+            }
+
+            routes.lock().unwrap_or_else(|e| e.into_inner())
+                .prune();
+        }
+    }
+
+    pub(crate) fn socket(&self) -> &EkkoSocket {
+        &(self.socket)
+    }
+
+    /// Have packets for route delivered to inbox, including those that
+    /// arrived since the request was sent.
+    pub(crate) fn register(&self, route: EkkoRoute, inbox: &Arc<EkkoInbox>, since: Instant) {
+        let mut routes = self.routes.lock().unwrap_or_else(|e| e.into_inner());
+        let inboxes = routes.routes.entry(route).or_default();
+
+        // Everything since the earlier registration was delivered already.
+        if inboxes.iter().any(|registered| registered.as_ptr() == Arc::as_ptr(inbox)) {
+            return
+        }
+
+        inboxes.push(Arc::downgrade(inbox));
+
+        for (_, _, owned) in routes.recent.iter().filter(|(timepoint, recent, _)| {
+            *recent == route && *timepoint >= since
+        }) {
+            inbox.deliver(owned.clone());
+        }
+    }
+
+    pub(crate) fn unregister(&self, route: EkkoRoute, inbox: &Arc<EkkoInbox>) {
+        let mut routes = self.routes.lock().unwrap_or_else(|e| e.into_inner());
+
+        if let Some(inboxes) = routes.routes.get_mut(&(route)) {
+            inboxes.retain(|registered| !(registered.ptr_eq(&(Arc::downgrade(inbox)))));
+
+            if inboxes.is_empty() {
+                routes.routes.remove(&(route));
+            }
+        }
+    }
+}
+
+impl Drop for EkkoDispatcher {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+
+        // Nobody sends on the socket anymore, shutting it down wakes the
+        // thread right away instead of at its next poll.
+        self.socket.shutdown();
+
+        if let Some(thread) = self.thread.take() {
+            if thread.thread().id() != std::thread::current().id() {
+                thread.join().ok();
+            }
+        }
+    }
+}
+
+/// Inbox of a set of requests along with the dispatchers it is registered
+/// with, one for each address family requests are sent to.
+pub(crate) struct EkkoSubscription {
+    pub(crate) dispatchers: Vec<Arc<EkkoDispatcher>>,
+    pub(crate) inbox: Arc<EkkoInbox>,
+}
+
+impl EkkoSubscription {
+
+    /// Dispatcher of the socket requests to target are sent on.
+    pub(crate) fn dispatcher(&self, target: IpAddr) -> Option<&EkkoDispatcher> {
+        self.dispatchers.iter().map(AsRef::as_ref).find(|dispatcher| {
+            dispatcher.socket().source().is_ipv4() == target.is_ipv4()
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+
+        io::{ErrorKind},
+    };
+
+    use socket2::{
+
+        Protocol,
+        Domain,
+        Socket,
+        Type,
+    };
+
+    use crate::{
+
+        EkkoResponse,
+        EkkoError,
+        EkkoMode,
+        Ekko,
+    };
+
+    #[test]
+    fn datagram_handles() -> Result<(), EkkoError> {
+        // Datagram sockets are only permitted for groups in the range of
+        // net.ipv4.ping_group_range.
+        if let Err(e) = Socket::new(Domain::IPV4, Type::DGRAM, Some(Protocol::ICMPV4)) {
+            if e.kind() == ErrorKind::PermissionDenied {
+                return Ok(())
+            }
+        }
+
+        let senders = [
+            Ekko::with_target_and_mode([127, 0, 0, 1], EkkoMode::Datagram)?,
+            Ekko::with_target_and_mode([127, 0, 0, 1], EkkoMode::Datagram)?,
+        ];
+
+        // Both send the very same requests at once, each has to get the
+        // replies to its own.
+        let responses = std::thread::scope(|scope| {
+            let handles: Vec<_> = senders.iter().map(|sender| scope.spawn(move || {
+                sender.send_range(64..72)
+            })).collect();
+
+            handles.into_iter().map(|handle| handle.join().unwrap())
+                .collect::<Result<Vec<_>, EkkoError>>()
+        })?;
+
+        let identifiers: Vec<Vec<u16>> = responses.iter().map(|responses| {
+            responses.iter().map(|response| match response {
+                EkkoResponse::Destination(data) => data.identifier,
+                response => panic!("expected destination, got {:?}", response),
+            }).collect()
+        }).collect();
+
+        for identifiers in identifiers.iter() {
+            assert!(identifiers.iter().all(|identifier| *identifier == identifiers[0]));
+        }
+
+        assert_ne!(identifiers[0][0], identifiers[1][0]);
+
+        Ok(())
+    }
+}
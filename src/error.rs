@@ -6,14 +6,24 @@ pub enum EkkoError {
     SocketSendIcmpv4(String),
     #[error("Socket send, reason: {0}")]
     SocketSendIcmpv6(String),
+    #[error("Socket send exceeds the local MTU, reason: {0}")]
+    SocketSendTooBig(String),
     #[error("Failed to create socket, reason: {0}")]
     SocketCreateIcmpv4(String),
     #[error("Failed to create socket, reason: {0}")]
     SocketCreateIcmpv6(String),
+    #[error("Failed to create socket, reason: {0}")]
+    SocketCreateUdp(String),
+    #[error("Failed to create socket, reason: {0}")]
+    SocketCreateTcp(String),
     #[error("Socket failed binding to address [{0}], reason: {1}")]
     SocketBindIpv4(String, String),
     #[error("Socket failed binding to address [{0}], reason: {1}")]
     SocketBindIpv6(String, String),
+    #[error("Socket failed binding to device [{0}], reason: {1}")]
+    SocketBindDevice(String, String),
+    #[error("Could not scope target [{0}], reason: {1}")]
+    SocketScope(String, String),
     #[error("Cannot combine address [{src:?}] (source) with [{dst:?}] (target).")]
     SocketIpMismatch { src: String, dst: String },
     #[error("Socket returned no address for responder.")]
@@ -30,10 +40,34 @@ pub enum EkkoError {
     SocketSetMaxHopsIpv4(String),
     #[error("Could not set socket max hops, reason: {0}")]
     SocketSetMaxHopsIpv6(String),
+    #[error("Could not set sockets type of service, reason: {0}")]
+    SocketSetTosIpv4(String),
+    #[error("Could not set sockets traffic class, reason: {0}")]
+    SocketSetTosIpv6(String),
     #[error("Could not set socket send buffer size, reason: {0}")]
     SocketSendBufferSize(String),
+    #[error("Could not set sockets don't fragment bit, reason: {0}")]
+    SocketSetDontFragment(String),
+    #[error("Could not set sockets timestamping, reason: {0}")]
+    SocketSetTimestamps(String),
+    #[error("Could not include headers with requests, reason: {0}")]
+    SocketSetHeaderIncluded(String),
+    #[error("Could not set sockets receive filter, reason: {0}")]
+    SocketSetFilter(String),
+    #[error("Could not enable socket error queue, reason: {0}")]
+    SocketSetReceiveErrors(String),
+    #[error("Could not enable receiving time to live, reason: {0}")]
+    SocketSetReceiveTtl(String),
+    #[error("Could not read sockets local address, reason: {0}")]
+    SocketReadLocalAddress(String),
+    #[error("Could not start receiving from socket, reason: {0}")]
+    SocketDispatch(String),
+    #[error("Could not find a route to [{0}], reason: {1}")]
+    SocketRoute(String, String),
     #[error("Cannot combine address [{src:?}] (source) with [{dst:?}] (target).")]
     RequestIpMismatch { src: String, dst: String },
+    #[error("Packet too short for [{0}], needs at least {1} octets.")]
+    PacketTruncated(&'static str, usize),
     #[error("Failed to read response field [{0}], reason: {1}")]
     ResponseReadField(&'static str, String),
     #[error("Failed to read request field [{0}], reason: {1}")]
@@ -46,8 +80,27 @@ pub enum EkkoError {
     RequestWriteIcmpv4Field(&'static str, String),
     #[error("Failed to write request field [{0}], reason: {1}")]
     RequestWriteIcmpv6Field(&'static str, String),
+    #[error("Failed to write request field [{0}], reason: {1}")]
+    RequestWriteTcpField(&'static str, String),
+    #[error("Requests of this kind cannot be used for [{0}].")]
+    RequestUnsupported(&'static str),
+    #[error("Payload of {0} octets does not fit in a request, limit is {1} octets.")]
+    RequestPayloadLength(usize, usize),
     #[error("Failed to write request payload, reason: {0}")]
     RequestWriteIcmpv4Payload(String),
     #[error("Failed to write request payload, reason: {0}")]
     RequestWriteIcmpv6Payload(String),
-}
\ No newline at end of file
+}
+
+impl EkkoError {
+
+    /// Whether a packet received failed to parse, which is no reason to give
+    /// up on those received after it.
+    pub(crate) fn is_malformed(&self) -> bool {
+        matches!(self, EkkoError::PacketTruncated(..) |
+            EkkoError::ResponseReadField(..) |
+            EkkoError::RequestReadField(..) |
+            EkkoError::RequestReadIcmpv4Type(..) |
+            EkkoError::RequestReadIcmpv6Type(..))
+    }
+}
@@ -0,0 +1,589 @@
+use std::{
+
+    net::{
+
+        Ipv6Addr,
+        Ipv4Addr,
+        IpAddr,
+    },
+};
+
+use super::{
+
+    packets::{
+
+        EkkoPacket,
+        checksum,
+    },
+
+    error::{EkkoError},
+
+    responses::{
+
+        UnreachableCodeV4,
+        Unreachable,
+    },
+};
+
+/// Version of the control message protocol.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum IcmpVersion {
+    V4,
+    V6,
+}
+
+/// Control message quoted by an error message, if the quoted packet carries
+/// one and its internet protocol headers are complete.
+fn quoted(packet: EkkoPacket<'_>) -> Option<IcmpPacket<'_>> {
+    match packet.get_originator().ok()? {
+
+        EkkoPacket::V4(buf) => IcmpPacket::new(buf, IcmpVersion::V4).ok(),
+        EkkoPacket::V6(buf) => IcmpPacket::new(buf, IcmpVersion::V6).ok(),
+
+        EkkoPacket::Udp(_) | EkkoPacket::Tcp(_) => None,
+    }
+}
+
+/// Zero-copy view over a control message, starting at its header. Only the
+/// header is checked on construction, accessors check the rest.
+///
+/// ## Example
+/// ```rust
+/// use ekko::{
+///
+///     IcmpMessage,
+///     IcmpVersion,
+///     IcmpBuilder,
+///     IcmpPacket,
+///     EkkoError,
+/// };
+///
+/// fn main() -> Result<(), EkkoError> {
+///     let mut buf: [u8; 64] = [0; 64];
+///     let request = IcmpBuilder::echo_request(0xBAD, 1)
+///         .payload(b"Ekko")
+///         .build_v4(&mut buf)?;
+///
+///     let packet = IcmpPacket::new(request.as_bytes(), IcmpVersion::V4)?;
+///
+///     if let IcmpMessage::EchoRequest(echo) = packet.message()? {
+///         assert_eq!(echo.identifier()?, 0xBAD);
+///         assert_eq!(echo.payload(), b"Ekko");
+///     }
+///
+///     Ok(())
+/// }
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct IcmpPacket<'a> {
+
+    packet: EkkoPacket<'a>,
+}
+
+impl<'a> IcmpPacket<'a> {
+
+    /// View over given octets, fails if they do not hold a header.
+    pub fn new(buf: &'a [u8], version: IcmpVersion) -> Result<IcmpPacket<'a>, EkkoError> {
+        if buf.len() < 8 {
+            return Err(EkkoError::PacketTruncated("header", 8))
+        }
+
+        Ok(IcmpPacket {
+
+            packet: match version {
+                IcmpVersion::V4 => EkkoPacket::V4(buf),
+                IcmpVersion::V6 => EkkoPacket::V6(buf),
+            },
+        })
+    }
+
+    pub fn version(&self) -> IcmpVersion {
+        match self.packet {
+            EkkoPacket::V6(_) => IcmpVersion::V6,
+            _ => IcmpVersion::V4,
+        }
+    }
+
+    /// Message type.
+    pub fn kind(&self) -> Result<u8, EkkoError> {
+        self.packet.get_type()
+    }
+
+    pub fn code(&self) -> Result<u8, EkkoError> {
+        self.packet.get_code()
+    }
+
+    pub fn checksum(&self) -> Result<u16, EkkoError> {
+        self.packet.get_checksum()
+    }
+
+    /// Whether the checksum matches, ICMPv6 needs the source and destination
+    /// addresses and is never valid without them.
+    pub fn is_checksum_valid(&self, addresses: Option<(Ipv6Addr, Ipv6Addr)>) -> bool {
+        match (self.version(), addresses) {
+
+            (IcmpVersion::V4, _) => checksum(self.as_bytes(), None) == 0,
+            (IcmpVersion::V6, Some((source, destination))) => checksum(self.as_bytes(), {
+                Some((IpAddr::V6(source), IpAddr::V6(destination), 58))
+            }) == 0,
+
+            (IcmpVersion::V6, None) => false,
+        }
+    }
+
+    pub fn as_bytes(&self) -> &'a [u8] {
+        self.packet.as_slice()
+    }
+
+    /// Typed view of the message.
+    pub fn message(&self) -> Result<IcmpMessage<'a>, EkkoError> {
+        let packet = self.packet;
+
+        Ok(match (self.version(), self.kind()?) {
+
+            (IcmpVersion::V4, 8) | (IcmpVersion::V6, 128) => IcmpMessage::EchoRequest(IcmpEcho { packet }),
+            (IcmpVersion::V4, 0) | (IcmpVersion::V6, 129) => IcmpMessage::EchoReply(IcmpEcho { packet }),
+
+            (IcmpVersion::V4, 11) | (IcmpVersion::V6, 3) => IcmpMessage::TimeExceeded(IcmpTimeExceeded { packet }),
+            (IcmpVersion::V4, 3) | (IcmpVersion::V6, 1) => IcmpMessage::Unreachable(IcmpUnreachable { packet }),
+            (IcmpVersion::V4, 12) | (IcmpVersion::V6, 4) => IcmpMessage::ParameterProblem(IcmpParameterProblem { packet }),
+            (IcmpVersion::V6, 2) => IcmpMessage::PacketTooBig(IcmpPacketTooBig { packet }),
+
+            (IcmpVersion::V4, 5) => IcmpMessage::Redirect(IcmpRedirect { packet }),
+            (IcmpVersion::V6, 137) => {
+                if self.as_bytes().len() < 40 {
+                    return Err(EkkoError::PacketTruncated("redirect", 40))
+                }
+
+                IcmpMessage::Redirect(IcmpRedirect { packet })
+            },
+
+            _ => IcmpMessage::Other(*self),
+        })
+    }
+}
+
+/// Typed control messages, take a look at [`IcmpPacket::message`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum IcmpMessage<'a> {
+    EchoRequest(IcmpEcho<'a>),
+    EchoReply(IcmpEcho<'a>),
+    TimeExceeded(IcmpTimeExceeded<'a>),
+    Unreachable(IcmpUnreachable<'a>),
+    Redirect(IcmpRedirect<'a>),
+    /// Only exists for ICMPv6, ICMPv4 uses unreachable with code 4.
+    PacketTooBig(IcmpPacketTooBig<'a>),
+    ParameterProblem(IcmpParameterProblem<'a>),
+    /// Contains any other message.
+    Other(IcmpPacket<'a>),
+}
+
+/// Echo request or reply.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct IcmpEcho<'a> {
+
+    packet: EkkoPacket<'a>,
+}
+
+impl<'a> IcmpEcho<'a> {
+
+    pub fn identifier(&self) -> Result<u16, EkkoError> {
+        self.packet.get_identifier()
+    }
+
+    pub fn sequence(&self) -> Result<u16, EkkoError> {
+        self.packet.get_sequence()
+    }
+
+    pub fn payload(&self) -> &'a [u8] {
+        &(self.packet.as_slice()[8..])
+    }
+}
+
+/// Time exceeded, code 0 for hops and 1 for fragment reassembly.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct IcmpTimeExceeded<'a> {
+
+    packet: EkkoPacket<'a>,
+}
+
+impl<'a> IcmpTimeExceeded<'a> {
+
+    pub fn code(&self) -> Result<u8, EkkoError> {
+        self.packet.get_code()
+    }
+
+    /// Quoted packet, starting at its internet protocol header.
+    pub fn original(&self) -> &'a [u8] {
+        &(self.packet.as_slice()[8..])
+    }
+
+    /// Quoted control message, if the quoted packet is one.
+    pub fn original_icmp(&self) -> Option<IcmpPacket<'a>> {
+        quoted(self.packet)
+    }
+}
+
+/// Destination unreachable.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct IcmpUnreachable<'a> {
+
+    packet: EkkoPacket<'a>,
+}
+
+impl<'a> IcmpUnreachable<'a> {
+
+    pub fn code(&self) -> Result<u8, EkkoError> {
+        self.packet.get_code()
+    }
+
+    /// Next hops max transmission unit, only given by ICMPv4 with code 4
+    /// (fragmentation required).
+    pub fn next_hop_mtu(&self) -> Option<u16> {
+        match self.packet.get_unreachable() {
+            Ok(Unreachable::V4(UnreachableCodeV4::FragmentationRequired(mtu))) => Some(mtu),
+            _ => None,
+        }
+    }
+
+    /// Quoted packet, starting at its internet protocol header.
+    pub fn original(&self) -> &'a [u8] {
+        &(self.packet.as_slice()[8..])
+    }
+
+    /// Quoted control message, if the quoted packet is one.
+    pub fn original_icmp(&self) -> Option<IcmpPacket<'a>> {
+        quoted(self.packet)
+    }
+}
+
+/// Packet too big, ICMPv6 only.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct IcmpPacketTooBig<'a> {
+
+    packet: EkkoPacket<'a>,
+}
+
+impl<'a> IcmpPacketTooBig<'a> {
+
+    /// Next hops max transmission unit.
+    pub fn mtu(&self) -> Result<u32, EkkoError> {
+        self.packet.get_packet_too_big()
+    }
+
+    /// Quoted packet, starting at its internet protocol header.
+    pub fn original(&self) -> &'a [u8] {
+        &(self.packet.as_slice()[8..])
+    }
+
+    /// Quoted control message, if the quoted packet is one.
+    pub fn original_icmp(&self) -> Option<IcmpPacket<'a>> {
+        quoted(self.packet)
+    }
+}
+
+/// Parameter problem.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct IcmpParameterProblem<'a> {
+
+    packet: EkkoPacket<'a>,
+}
+
+impl<'a> IcmpParameterProblem<'a> {
+
+    pub fn code(&self) -> Result<u8, EkkoError> {
+        self.packet.get_code()
+    }
+
+    /// Offset of the problem in the quoted packet.
+    pub fn pointer(&self) -> Result<u32, EkkoError> {
+        self.packet.get_parameter_problem()
+    }
+
+    /// Quoted packet, starting at its internet protocol header.
+    pub fn original(&self) -> &'a [u8] {
+        &(self.packet.as_slice()[8..])
+    }
+
+    /// Quoted control message, if the quoted packet is one.
+    pub fn original_icmp(&self) -> Option<IcmpPacket<'a>> {
+        quoted(self.packet)
+    }
+}
+
+/// Redirect, ICMPv6 redirects are part of neighbor discovery.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct IcmpRedirect<'a> {
+
+    packet: EkkoPacket<'a>,
+}
+
+impl<'a> IcmpRedirect<'a> {
+
+    pub fn code(&self) -> Result<u8, EkkoError> {
+        self.packet.get_code()
+    }
+
+    /// Better first hop, the target address for ICMPv6.
+    pub fn gateway(&self) -> Result<IpAddr, EkkoError> {
+        self.packet.get_gateway().map(|(gateway, _)| gateway)
+    }
+
+    /// Destination being redirected, ICMPv4 only names it in the quoted packet.
+    pub fn destination(&self) -> Option<IpAddr> {
+        self.packet.get_gateway().ok()
+            .and_then(|(_, destination)| destination)
+    }
+
+    /// Quoted packet for ICMPv4, neighbor discovery options for ICMPv6.
+    pub fn original(&self) -> &'a [u8] {
+        match self.packet {
+            EkkoPacket::V6(buf) => &(buf[40..]),
+            packet => &(packet.as_slice()[8..]),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum IcmpKind {
+    EchoRequest,
+    EchoReply,
+    TimeExceeded,
+    Unreachable,
+    Redirect,
+    PacketTooBig,
+    ParameterProblem,
+}
+
+/// Builder for control messages, writes into a given buffer.
+#[derive(Clone, Debug)]
+pub struct IcmpBuilder<'b> {
+
+    kind: IcmpKind,
+    code: u8,
+
+    rest: [u8; 4],
+    payload: &'b [u8],
+}
+
+impl<'b> IcmpBuilder<'b> {
+
+    fn with_kind(kind: IcmpKind, code: u8, rest: [u8; 4]) -> IcmpBuilder<'b> {
+        IcmpBuilder {
+
+            kind,
+            code,
+
+            rest,
+            payload: &[],
+        }
+    }
+
+    pub fn echo_request(identifier: u16, sequence: u16) -> IcmpBuilder<'b> {
+        let [a, b] = identifier.to_be_bytes();
+        let [c, d] = sequence.to_be_bytes();
+
+        IcmpBuilder::with_kind(IcmpKind::EchoRequest, 0, [a, b, c, d])
+    }
+
+    pub fn echo_reply(identifier: u16, sequence: u16) -> IcmpBuilder<'b> {
+        let [a, b] = identifier.to_be_bytes();
+        let [c, d] = sequence.to_be_bytes();
+
+        IcmpBuilder::with_kind(IcmpKind::EchoReply, 0, [a, b, c, d])
+    }
+
+    pub fn time_exceeded(code: u8) -> IcmpBuilder<'b> {
+        IcmpBuilder::with_kind(IcmpKind::TimeExceeded, code, [0; 4])
+    }
+
+    pub fn unreachable(code: u8) -> IcmpBuilder<'b> {
+        IcmpBuilder::with_kind(IcmpKind::Unreachable, code, [0; 4])
+    }
+
+    /// Packet too big for ICMPv6, unreachable with code 4 (fragmentation
+    /// required) for ICMPv4, which only holds 16 bits of the mtu.
+    pub fn packet_too_big(mtu: u32) -> IcmpBuilder<'b> {
+        IcmpBuilder::with_kind(IcmpKind::PacketTooBig, 0, mtu.to_be_bytes())
+    }
+
+    pub fn parameter_problem(code: u8, pointer: u32) -> IcmpBuilder<'b> {
+        IcmpBuilder::with_kind(IcmpKind::ParameterProblem, code, pointer.to_be_bytes())
+    }
+
+    /// Redirect to given gateway, ICMPv4 only.
+    pub fn redirect(code: u8, gateway: Ipv4Addr) -> IcmpBuilder<'b> {
+        IcmpBuilder::with_kind(IcmpKind::Redirect, code, gateway.octets())
+    }
+
+    /// Payload of echo messages, the quoted packet of error messages.
+    pub fn payload(mut self, payload: &'b [u8]) -> IcmpBuilder<'b> {
+        self.payload = payload;
+        self
+    }
+
+    /// Write an ICMPv4 message into buffer, returns a view over the written
+    /// octets.
+    pub fn build_v4<'a>(&self, buf: &'a mut [u8]) -> Result<IcmpPacket<'a>, EkkoError> {
+        let header = match self.kind {
+
+            IcmpKind::EchoRequest => (8, self.code, self.rest),
+            IcmpKind::EchoReply => (0, self.code, self.rest),
+            IcmpKind::TimeExceeded => (11, self.code, self.rest),
+            IcmpKind::Unreachable => (3, self.code, self.rest),
+            IcmpKind::Redirect => (5, self.code, self.rest),
+
+            IcmpKind::PacketTooBig => {
+                let [_, _, c, d] = self.rest;
+                (3, 4, [0, 0, c, d])
+            },
+
+            IcmpKind::ParameterProblem => {
+                let [_, _, _, d] = self.rest;
+                (12, self.code, [d, 0, 0, 0])
+            },
+        };
+
+        Ok(IcmpPacket {
+
+            packet: EkkoPacket::new_message(buf, header, self.payload, None)?,
+        })
+    }
+
+    /// Write an ICMPv6 message into buffer, the checksum covers the source
+    /// and destination addresses. Returns a view over the written octets.
+    pub fn build_v6<'a>(&self, buf: &'a mut [u8], addresses: (Ipv6Addr, Ipv6Addr)) -> Result<IcmpPacket<'a>, EkkoError> {
+        let kind = match self.kind {
+
+            IcmpKind::EchoRequest => 128,
+            IcmpKind::EchoReply => 129,
+            IcmpKind::TimeExceeded => 3,
+            IcmpKind::Unreachable => 1,
+            IcmpKind::PacketTooBig => 2,
+            IcmpKind::ParameterProblem => 4,
+
+            IcmpKind::Redirect => return Err(EkkoError::RequestWriteIcmpv6Field("type", {
+                "redirects are part of neighbor discovery".to_owned()
+            })),
+        };
+
+        Ok(IcmpPacket {
+
+            packet: EkkoPacket::new_message(buf, (kind, self.code, self.rest), self.payload, {
+                Some(addresses)
+            })?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::net::{Ipv6Addr};
+
+    use super::{
+
+        IcmpMessage,
+        IcmpVersion,
+        IcmpBuilder,
+        IcmpPacket,
+    };
+
+    use crate::{EkkoError};
+
+    #[test]
+    fn echo_v4() -> Result<(), EkkoError> {
+        let mut buf: [u8; 64] = [0; 64];
+        let packet = IcmpBuilder::echo_reply(0xBAD, 7)
+            .payload(b"Ekko, ekko, ekko ..")
+            .build_v4(&mut buf)?;
+
+        assert!(packet.is_checksum_valid(None));
+
+        match packet.message()? {
+
+            IcmpMessage::EchoReply(echo) => {
+                assert_eq!(echo.identifier()?, 0xBAD);
+                assert_eq!(echo.sequence()?, 7);
+                assert_eq!(echo.payload(), b"Ekko, ekko, ekko ..");
+            },
+
+            message => panic!("unexpected message: {:?}", message),
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn quoted_v6() -> Result<(), EkkoError> {
+        let source = Ipv6Addr::new(0xFD00, 1, 0, 0, 0, 0, 0, 2);
+        let destination = Ipv6Addr::new(0xFD00, 3, 0, 0, 0, 0, 0, 2);
+
+        let mut request: [u8; 16] = [0; 16];
+        let request = IcmpBuilder::echo_request(0xBAD, 1)
+            .build_v6(&mut request, (source, destination))?;
+
+        let mut original: [u8; 48] = [0; 48];
+        original[0] = 0x60;
+        original[6] = 58;
+        original[40..48].copy_from_slice(request.as_bytes());
+
+        let mut buf: [u8; 64] = [0; 64];
+        let packet = IcmpBuilder::packet_too_big(1280)
+            .payload(&(original))
+            .build_v6(&mut buf, (destination, source))?;
+
+        assert!(packet.is_checksum_valid(Some((destination, source))));
+        assert!(!(packet.is_checksum_valid(None)));
+
+        match IcmpPacket::new(packet.as_bytes(), IcmpVersion::V6)?.message()? {
+
+            IcmpMessage::PacketTooBig(message) => {
+                assert_eq!(message.mtu()?, 1280);
+                assert_eq!(message.original_icmp(), Some(request));
+            },
+
+            message => panic!("unexpected message: {:?}", message),
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn quoted_fragment_v6() -> Result<(), EkkoError> {
+        let source = Ipv6Addr::new(0xFD00, 1, 0, 0, 0, 0, 0, 2);
+        let destination = Ipv6Addr::new(0xFD00, 3, 0, 0, 0, 0, 0, 2);
+
+        let mut request: [u8; 16] = [0; 16];
+        let request = IcmpBuilder::echo_request(0xBAD, 1)
+            .build_v6(&mut request, (source, destination))?;
+
+        // Fragment header between the quoted header and the request.
+        let mut original: [u8; 56] = [0; 56];
+        original[0] = 0x60;
+        original[6] = 44;
+        original[40] = 58;
+        original[48..56].copy_from_slice(request.as_bytes());
+
+        let mut buf: [u8; 64] = [0; 64];
+        let packet = IcmpBuilder::time_exceeded(0)
+            .payload(&(original))
+            .build_v6(&mut buf, (destination, source))?;
+
+        match packet.message()? {
+
+            IcmpMessage::TimeExceeded(message) => {
+                assert_eq!(message.original_icmp(), Some(request));
+            },
+
+            message => panic!("unexpected message: {:?}", message),
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn truncated() {
+        assert!(IcmpPacket::new(&[11, 0, 0], IcmpVersion::V4).is_err());
+        assert!(IcmpPacket::new(&[137, 0, 0, 0, 0, 0, 0, 0], IcmpVersion::V6).unwrap().message().is_err());
+    }
+}
@@ -4,39 +4,42 @@
 //! ```rust,no_run
 //! use ekko::{ 
 //! 
-//!     EkkoResponse,
+//!     Traceroute,
 //!     EkkoError,
 //!     Ekko,
 //! };
 //! 
 //! fn main() -> Result<(), EkkoError> {
 //!     let sender = Ekko::with_target([8, 8, 8, 8])?;
+//!     let trace = Traceroute::default()
+//!         .max_hops(32)
+//!         .trace(&sender)?;
 //! 
-//!     for hops in 0..32 {
-//!         let responses = sender.send_range(0..hops)?;
-//!         for ekko in responses.iter() {
-//!             match ekko {
-//! 
-//!                 EkkoResponse::Destination(_) => {
-//!                     for ekko in responses.iter() {
-//!                         println!("{:?}", ekko)
-//!                     }
-//!     
-//!                     return Ok(()) 
-//!                 }
-//! 
-//!                 ekko => continue
-//!             }
-//!         }
+//!     for hop in trace.hops.iter() {
+//!         println!("{} {:?}", hop.hops, hop.responders())
 //!     }
 //! 
 //!     Ok(())
 //! }
 //! ```
 
+#[cfg(all(feature = "async", unix))]
+mod asynchronous;
+
+mod traceroute;
+mod multipath;
+mod mtu;
+mod stats;
+mod session;
+mod stream;
+mod icmp;
 mod responses;
+mod requests;
 mod packets;
 mod sender;
+mod dispatcher;
+mod multi;
+mod socket;
 mod error;
 
 pub use error::{EkkoError};
@@ -44,9 +47,66 @@ pub use error::{EkkoError};
 pub use sender::{
 
     EkkoSettings,
+    EkkoBuilder,
+    EkkoPattern,
+    EkkoEcn,
     Ekko,
 };
 
+pub use socket::{
+
+    EkkoProbe,
+    EkkoMode,
+};
+pub use multi::{MultiEkko};
+pub use stats::{EkkoStats};
+pub use session::{EkkoSession};
+pub use stream::{EkkoStream};
+
+pub use traceroute::{
+
+    TraceModification,
+    Traceroute,
+    TraceHop,
+    TraceEcn,
+    Trace,
+};
+
+pub use multipath::{
+
+    MultipathTrace,
+    MultipathHop,
+    Multipath,
+};
+
+pub use mtu::{
+
+    MtuDiscovery,
+    PathMtu,
+};
+
+pub use icmp::{
+
+    IcmpParameterProblem,
+    IcmpTimeExceeded,
+    IcmpPacketTooBig,
+    IcmpUnreachable,
+    IcmpRedirect,
+    IcmpEcho,
+
+    IcmpMessage,
+    IcmpVersion,
+    IcmpBuilder,
+    IcmpPacket,
+};
+
+#[cfg(all(feature = "async", unix))]
+pub use asynchronous::{
+
+    AsyncEkkoStream,
+    AsyncEkko,
+};
+
 pub use responses::{
 
     UnreachableCodeV6,
@@ -55,5 +115,6 @@ pub use responses::{
     Redirect,
 
     EkkoResponse,
+    EkkoHeader,
     EkkoData,
 };
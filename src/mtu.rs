@@ -0,0 +1,265 @@
+use std::{
+
+    net::{IpAddr},
+    time::{
+
+        Duration,
+        Instant,
+    },
+};
+
+use super::{
+
+    error::{EkkoError},
+    socket::{EkkoProbe},
+    sender::{Ekko},
+
+    responses::{
+
+        UnreachableCodeV4,
+        EkkoResponse,
+        Unreachable,
+    },
+};
+
+/// Result of path MTU discovery.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PathMtu {
+    /// Largest request, internet protocol header included, that reached the
+    /// target. None when not even the smallest request got through.
+    pub mtu: Option<usize>,
+    /// Responder refusing larger requests, none when the local interface
+    /// refused them or nobody responded.
+    pub hop: Option<IpAddr>,
+    /// Larger requests vanished without any error message coming back, at
+    /// some point of the search. Stays set when other sizes were refused
+    /// later on.
+    pub black_hole: bool,
+}
+
+/// What became of requests of a single size.
+enum Outcome {
+    /// Reached the target.
+    Fits,
+    /// Refused by responder, along with the MTU it reported.
+    TooBig(Option<IpAddr>, Option<usize>),
+    /// Went unanswered.
+    Silent,
+}
+
+/// Path MTU discovery builder, take a look at the default implementation.
+///
+/// ## Example
+/// ```rust,no_run
+/// use ekko::{
+///
+///     MtuDiscovery,
+///     EkkoError,
+///     Ekko,
+/// };
+///
+/// fn main() -> Result<(), EkkoError> {
+///     let sender = Ekko::with_target([8, 8, 8, 8])?;
+///     let path = MtuDiscovery::default()
+///         .max_mtu(1500)
+///         .discover_path_mtu(&sender)?;
+///
+///     println!("{:?} capped by {:?}", path.mtu, path.hop);
+///
+///     Ok(())
+/// }
+/// ```
+#[derive(Clone, Debug)]
+pub struct MtuDiscovery {
+
+    min_mtu: Option<usize>,
+    max_mtu: usize,
+
+    queries: usize,
+    hops: u32,
+
+    timeout: Duration,
+}
+
+impl Default for MtuDiscovery {
+    fn default() -> MtuDiscovery {
+        MtuDiscovery {
+
+            min_mtu: None,
+            max_mtu: 9000,
+
+            queries: 3,
+            hops: 64,
+
+            timeout: {
+
+                Duration::from_millis(1000)
+            },
+        }
+    }
+}
+
+impl MtuDiscovery {
+
+    /// Size assumed to get through, 68 for IPv4 and 1280 for IPv6 unless set.
+    pub fn min_mtu(mut self, mtu: usize) -> MtuDiscovery {
+        self.min_mtu = Some(mtu);
+        self
+    }
+
+    /// Largest size to try, jumbo frames by default.
+    pub fn max_mtu(mut self, mtu: usize) -> MtuDiscovery {
+        self.max_mtu = mtu.min(65535);
+        self
+    }
+
+    /// Number of requests sent for each size, a size counts as lost only
+    /// when all of them go unanswered.
+    pub fn queries(mut self, queries: usize) -> MtuDiscovery {
+        self.queries = queries.max(1);
+        self
+    }
+
+    /// Number of hops of each request.
+    pub fn hops(mut self, hops: u32) -> MtuDiscovery {
+        self.hops = hops;
+        self
+    }
+
+    /// Time to wait for responses to each size.
+    pub fn timeout(mut self, timeout: Duration) -> MtuDiscovery {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Discover the path MTU to the senders target by a binary search over
+    /// request sizes with the don't fragment bit set. Sizes refused by a
+    /// responder narrow the search to the MTU it reports, sizes going
+    /// unanswered while smaller ones get through point at a black hole.
+    pub fn discover_path_mtu(&self, sender: &Ekko) -> Result<PathMtu, EkkoError> {
+        if let EkkoProbe::Tcp(_) = sender.probe() {
+            return Err(EkkoError::RequestUnsupported("path MTU discovery"))
+        }
+
+        // Other senders sharing the socket must not have the bit set.
+        let sender = sender.inner_detached()?;
+
+        sender.inner_dont_fragment(true)?;
+        self.discover_inner(&(sender))
+    }
+
+    fn discover_inner(&self, sender: &Ekko) -> Result<PathMtu, EkkoError> {
+        let (header_octets, min_mtu) = match sender.target() {
+            IpAddr::V4(_) => (20 + 8, 68),
+            IpAddr::V6(_) => (40 + 8, 1280),
+        };
+
+        let min_mtu = self.min_mtu.unwrap_or(min_mtu).max(header_octets);
+        let max_mtu = self.max_mtu.max(min_mtu);
+
+        let identifier: u16 = rand::random();
+        let mut sequence: u16 = 0;
+
+        sender.inner_reserve(self.queries, max_mtu)?;
+
+        let mut probe = |size: usize| -> Result<Outcome, EkkoError> {
+            let payload = vec![0; size - header_octets];
+            let mut requests = sender.inner_requests(self.queries);
+
+            for _ in 0..self.queries {
+
+                let timepoint = Instant::now();
+                let pkt = match sender.inner_send(self.hops, 0, (identifier, sequence), None, &(payload)) {
+                    Err(EkkoError::SocketSendTooBig(_)) => return Ok(Outcome::TooBig(None, None)),
+                    result => result?,
+                };
+
+                requests.push(timepoint, sender.target(), pkt, self.hops);
+                sequence = sequence.wrapping_add(1);
+            }
+
+            sender.inner_wait(&mut requests, Instant::now() + self.timeout)?;
+
+            let mut outcome = Outcome::Silent;
+            for response in requests.finish() {
+                match response {
+
+                    EkkoResponse::Destination(_) => {
+                        return Ok(Outcome::Fits)
+                    },
+
+                    EkkoResponse::Unreachable((_, Unreachable::V4(UnreachableCodeV4::FragmentationRequired(_)))) |
+                    EkkoResponse::PacketTooBig(_) => {
+                        outcome = Outcome::TooBig(response.data().address, {
+                            response.mtu().map(|mtu| mtu as usize)
+                        });
+                    },
+
+                    _ => (),
+                }
+            }
+
+            Ok(outcome)
+        };
+
+        if !(matches!(probe(min_mtu)?, Outcome::Fits)) {
+            return Ok(PathMtu {
+
+                mtu: None,
+                hop: None,
+
+                black_hole: false,
+            })
+        }
+
+        let mut path = PathMtu {
+
+            mtu: Some(min_mtu),
+            hop: None,
+
+            black_hole: false,
+        };
+
+        // Sizes up to low got through, sizes beyond high did not.
+        let (mut low, mut high) = (min_mtu, max_mtu);
+        let mut size = max_mtu;
+
+        while low < high {
+            match probe(size)? {
+
+                Outcome::Fits => {
+                    low = size;
+                    size = low + (high - low).div_ceil(2);
+                },
+
+                Outcome::TooBig(hop, mtu) => {
+                    path.hop = hop;
+
+                    high = size - 1;
+
+                    // Try the reported MTU next, it is most likely the answer.
+                    size = match mtu {
+                        Some(mtu) if mtu > low && mtu < size => {
+                            high = mtu;
+                            mtu
+                        },
+
+                        _ => low + (high - low).div_ceil(2),
+                    };
+                },
+
+                Outcome::Silent => {
+                    path.black_hole = true;
+                    path.hop = None;
+
+                    high = size - 1;
+                    size = low + (high - low).div_ceil(2);
+                },
+            }
+        }
+
+        path.mtu = Some(low);
+
+        Ok(path)
+    }
+}
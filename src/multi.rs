@@ -0,0 +1,241 @@
+use std::{
+
+    sync::{Arc},
+
+    time::{
+
+        Duration,
+        Instant,
+    },
+
+    net::{
+
+        SocketAddrV6,
+        SocketAddrV4,
+        SocketAddr,
+    },
+
+    net::{
+
+        Ipv6Addr,
+        Ipv4Addr,
+        IpAddr,
+    },
+};
+
+use super::{
+
+    requests::{EkkoRequests},
+    sender::{EkkoSettings},
+
+    packets::{
+
+        EkkoPacket,
+        PAYLOAD,
+        payload,
+    },
+
+    dispatcher::{
+
+        EkkoSubscription,
+        EkkoDispatcher,
+    },
+
+    error::{EkkoError},
+
+    socket::{
+
+        EkkoProbe,
+        EkkoSent,
+        EkkoMode,
+    },
+
+    responses::{EkkoResponse},
+};
+
+/// Sender for many targets at once, shares one socket per address family
+/// with every other sender of the process and sends a single echo request to
+/// every target each round.
+///
+/// ## Example
+/// ```rust,no_run
+/// use ekko::{
+///
+///     EkkoError,
+///     MultiEkko,
+/// };
+///
+/// fn main() -> Result<(), EkkoError> {
+///     let sender = MultiEkko::with_targets(vec![[8, 8, 8, 8], [1, 1, 1, 1]])?;
+///
+///     for ekko in sender.send(64)? {
+///         println!("{:?}", ekko)
+///     }
+///
+///     Ok(())
+/// }
+/// ```
+pub struct MultiEkko {
+
+    target_socket_addresses: Vec<SocketAddr>,
+    interval: Duration,
+
+    dispatcher_v4: Option<Arc<EkkoDispatcher>>,
+    dispatcher_v6: Option<Arc<EkkoDispatcher>>,
+}
+
+impl MultiEkko {
+
+    /// Build a sender with given target addresses, uses raw sockets when
+    /// permitted and falls back to unprivileged datagram sockets otherwise.
+    pub fn with_targets<I, T>(targets: I) -> Result<MultiEkko, EkkoError>
+    where I: IntoIterator<Item = T>, T: Into<IpAddr> {
+        MultiEkko::with_targets_inner(targets.into_iter().map(Into::into).collect(), None)
+    }
+
+    /// Build a sender with given target addresses and socket mode.
+    pub fn with_targets_and_mode<I, T>(targets: I, mode: EkkoMode) -> Result<MultiEkko, EkkoError>
+    where I: IntoIterator<Item = T>, T: Into<IpAddr> {
+        MultiEkko::with_targets_inner(targets.into_iter().map(Into::into).collect(), Some(mode))
+    }
+
+    fn with_targets_inner(targets: Vec<IpAddr>, mode: Option<EkkoMode>) -> Result<MultiEkko, EkkoError> {
+        let targets_v4 = targets.iter().filter(|target| target.is_ipv4()).count();
+        let targets_v6 = targets.len() - targets_v4;
+
+        let dispatcher_v4 = match targets_v4 {
+
+            0 => None,
+            n => Some({
+
+                let source_address = SocketAddrV4::new(Ipv4Addr::new(0, 0, 0, 0), 0);
+                let dispatcher = EkkoDispatcher::shared(SocketAddr::V4(source_address), None, mode, EkkoProbe::Icmp)?;
+
+                dispatcher.socket().reserve(n, PAYLOAD.len())?;
+                dispatcher
+            }),
+        };
+
+        let dispatcher_v6 = match targets_v6 {
+
+            0 => None,
+            n => Some({
+
+                let source_address = SocketAddrV6::new(Ipv6Addr::new(0, 0, 0, 0, 0, 0, 0, 0), 0, 0, 0);
+                let dispatcher = EkkoDispatcher::shared(SocketAddr::V6(source_address), None, mode, EkkoProbe::Icmp)?;
+
+                dispatcher.socket().reserve(n, PAYLOAD.len())?;
+                dispatcher
+            }),
+        };
+
+        Ok(MultiEkko {
+
+            target_socket_addresses: targets.into_iter().map(|target| match target {
+                IpAddr::V4(target) => SocketAddr::V4(SocketAddrV4::new(target, 0)),
+                IpAddr::V6(target) => SocketAddr::V6(SocketAddrV6::new(target, 0, 0, 0)),
+            }).collect(),
+
+            interval: Duration::from_millis(0),
+
+            dispatcher_v4,
+            dispatcher_v6,
+        })
+    }
+
+    /// Set time to wait between consecutive requests, none by default.
+    pub fn set_interval(&mut self, interval: Duration) {
+        self.interval = interval;
+    }
+
+    /// Target addresses in the order responses are returned.
+    pub fn targets(&self) -> Vec<IpAddr> {
+        self.target_socket_addresses.iter()
+            .map(SocketAddr::ip)
+            .collect()
+    }
+
+    /// Send an echo request to every target with default settings.
+    pub fn send(&self, hops: u32) -> Result<Vec<EkkoResponse>, EkkoError> {
+        self.send_with_settings(hops, Default::default())
+    }
+
+    /// Send an echo request to every target with user defined settings, each
+    /// target is given its own sequence starting from the one in settings.
+    /// Returns one response per target, in the order of targets.
+    pub fn send_with_settings(&self, hops: u32, EkkoSettings {
+        timeout, identifier, mut sequence, flow, length, pattern, tos
+    }: EkkoSettings) -> Result<Vec<EkkoResponse>, EkkoError> {
+
+        let dispatchers: Vec<Arc<EkkoDispatcher>> = self.dispatcher_v4.iter()
+            .chain(self.dispatcher_v6.iter())
+            .cloned()
+            .collect();
+
+        for dispatcher in dispatchers.iter() {
+            dispatcher.socket().reserve(self.target_socket_addresses.len(), length)?;
+        }
+
+        let mut requests = EkkoRequests::subscribed(self.target_socket_addresses.len(), EkkoSubscription {
+            dispatchers,
+            inbox: Default::default(),
+        });
+
+        let mut deadline = Instant::now() + timeout;
+
+        for target in self.target_socket_addresses.iter() {
+
+            let socket = self.dispatcher(target)?.socket();
+            let payload = payload(target.ip(), flow, {
+                (length, &(pattern))
+            })?;
+
+            let mut request: Vec<u8> = vec![0; 10 + payload.len()];
+            let packet = EkkoPacket::new(&mut request[..], (identifier, sequence), flow, &(payload), {
+                (socket.source(), *target)
+            })?;
+
+            let timepoint = Instant::now();
+            let pkt = socket.send(*target, hops, tos, {
+                (packet.as_slice(), (identifier, sequence))
+            })?;
+
+            // Kernel timestamps are left to senders asking for them.
+            requests.push(timepoint, target.ip(), EkkoSent { key: None, ..pkt }, hops);
+            sequence = sequence.wrapping_add(1);
+
+            while timepoint.elapsed() < self.interval {
+                MultiEkko::inner_wait(&mut requests, timepoint + self.interval)?;
+            }
+
+            deadline = timepoint + timeout;
+        }
+
+        while !(requests.is_complete()) && Instant::now() < deadline {
+            MultiEkko::inner_wait(&mut requests, deadline)?;
+        }
+
+        Ok(requests.finish())
+    }
+
+    /// Packets failing to parse are skipped, they are no reason to give up
+    /// on every other target.
+    fn inner_wait(requests: &mut EkkoRequests, deadline: Instant) -> Result<(), EkkoError> {
+        match requests.wait(deadline) {
+            Err(e) if e.is_malformed() => Ok(()),
+            result => result,
+        }
+    }
+
+    fn dispatcher(&self, target: &SocketAddr) -> Result<&EkkoDispatcher, EkkoError> {
+        let dispatcher = match target {
+            SocketAddr::V4(_) => self.dispatcher_v4.as_deref(),
+            SocketAddr::V6(_) => self.dispatcher_v6.as_deref(),
+        };
+
+        dispatcher.ok_or_else(|| EkkoError::SocketIpMismatch {
+            src: "unspecified".to_owned(),
+            dst: target.to_string(),
+        })
+    }
+}
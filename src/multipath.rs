@@ -0,0 +1,308 @@
+use std::{
+
+    ops::{Range},
+
+    time::{
+
+        Duration,
+        Instant,
+    },
+
+    net::{IpAddr},
+};
+
+use super::{
+
+    packets::{PAYLOAD},
+    socket::{EkkoProbe},
+    error::{EkkoError},
+    sender::{Ekko},
+
+    responses::{EkkoResponse},
+};
+
+/// Responders and links found for a single number of hops.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MultipathHop {
+    /// Number of hops.
+    pub hops: u32,
+    /// Distinct responders in order of appearance.
+    pub responders: Vec<IpAddr>,
+    /// Links from responders of the previous hop to responders of this hop,
+    /// found by requests of the same flow reaching both.
+    pub links: Vec<(IpAddr, IpAddr)>,
+    /// Number of flows probed.
+    pub flows: usize,
+}
+
+/// Result of multipath detection, a graph of responders for each hop.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MultipathTrace {
+    /// Probed hops in order, ends at the first hop that reached the
+    /// destination or responded with unreachable.
+    pub hops: Vec<MultipathHop>,
+}
+
+impl MultipathTrace {
+
+    /// Responders linked to from given responder.
+    pub fn successors(&self, address: IpAddr) -> Vec<IpAddr> {
+        self.hops.iter()
+            .flat_map(|hop| hop.links.iter())
+            .filter(|(source, _)| source == &(address))
+            .map(|(_, target)| *target)
+            .collect()
+    }
+}
+
+/// Multipath detection algorithm (MDA) builder, take a look at the default
+/// implementation.
+///
+/// Every request belongs to a flow that keeps its checksum fixed, varying the
+/// flow on purpose makes load balancers spread requests over all their next
+/// hops. Flows are added to each hop until the number of responders seen rules
+/// out one more with the given confidence.
+///
+/// ## Example
+/// ```rust,no_run
+/// use ekko::{
+///
+///     EkkoError,
+///     Multipath,
+///     Ekko,
+/// };
+///
+/// fn main() -> Result<(), EkkoError> {
+///     let sender = Ekko::with_target([8, 8, 8, 8])?;
+///     let trace = Multipath::default()
+///         .confidence(0.99)
+///         .trace(&sender)?;
+///
+///     for hop in trace.hops.iter() {
+///         println!("{} {:?}", hop.hops, hop.links)
+///     }
+///
+///     Ok(())
+/// }
+/// ```
+#[derive(Clone, Debug)]
+pub struct Multipath {
+
+    first_hop: u32,
+    max_hops: u32,
+
+    confidence: f64,
+    max_flows: usize,
+
+    timeout: Duration,
+}
+
+impl Default for Multipath {
+    fn default() -> Multipath {
+        Multipath {
+
+            first_hop: 1,
+            max_hops: 30,
+
+            confidence: 0.95,
+            max_flows: 128,
+
+            timeout: {
+
+                Duration::from_millis(1000)
+            },
+        }
+    }
+}
+
+impl Multipath {
+
+    /// Number of hops to start from.
+    pub fn first_hop(mut self, hops: u32) -> Multipath {
+        self.first_hop = hops;
+        self
+    }
+
+    /// Number of hops to give up after.
+    pub fn max_hops(mut self, hops: u32) -> Multipath {
+        self.max_hops = hops;
+        self
+    }
+
+    /// Probability of having found all responders of a hop, between 0 and 1.
+    pub fn confidence(mut self, confidence: f64) -> Multipath {
+        self.confidence = confidence.clamp(0.0, 0.9999);
+        self
+    }
+
+    /// Upper bound of flows probed for each number of hops.
+    pub fn max_flows(mut self, flows: usize) -> Multipath {
+        self.max_flows = flows.max(1);
+        self
+    }
+
+    /// Time to wait for responses to each batch of flows.
+    pub fn timeout(mut self, timeout: Duration) -> Multipath {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Enumerate all paths to the senders target, stops at the first hop that
+    /// reached the destination or responded with unreachable. Not supported
+    /// for datagrams, which are sent to a new port for every request.
+    pub fn trace(&self, sender: &Ekko) -> Result<MultipathTrace, EkkoError> {
+        if let EkkoProbe::Udp(_) = sender.probe() {
+            return Err(EkkoError::RequestUnsupported("multipath tracing"))
+        }
+
+        let identifier: u16 = rand::random();
+        let flow: u16 = rand::random();
+
+        let mut sequence: u16 = 0;
+        let mut probe = |hop: u32, flows: Range<usize>| -> Result<Vec<EkkoResponse>, EkkoError> {
+            let mut requests = sender.inner_requests(flows.len());
+
+            for index in flows {
+
+                let timepoint = Instant::now();
+                let pkt = sender.inner_send(hop, 0, {
+                    (identifier, sequence)
+                }, Some(flow.wrapping_add(index as u16)), PAYLOAD)?;
+
+                requests.push(timepoint, sender.target(), pkt, hop);
+                sequence = sequence.wrapping_add(1);
+            }
+
+            sender.inner_wait(&mut requests, Instant::now() + self.timeout)?;
+
+            Ok(requests.finish())
+        };
+
+        sender.inner_reserve(self.max_flows, PAYLOAD.len())?;
+
+        // Responder of each flow, for each number of hops.
+        let mut flows: Vec<(u32, Vec<Option<IpAddr>>)> = Vec::new();
+
+        for hop in self.first_hop..=self.max_hops {
+
+            let mut responders: Vec<Option<IpAddr>> = Vec::new();
+            let mut is_final = false;
+
+            loop {
+
+                let needed = stopping_point(distinct(&(responders)).len().max(1), self.confidence)
+                    .min(self.max_flows);
+
+                if responders.len() >= needed {
+                    break
+                }
+
+                for response in probe(hop, responders.len()..needed)? {
+                    is_final |= matches!(response, EkkoResponse::Destination(_) | EkkoResponse::Unreachable(_));
+                    responders.push(response.data().address);
+                }
+            }
+
+            // Flows new to this hop are probed on the previous hop as well,
+            // so every responder found here can be linked to a predecessor.
+            if let Some((previous_hop, previous)) = flows.last_mut() {
+                if previous.len() < responders.len() && previous.iter().any(Option::is_some) {
+                    for response in probe(*previous_hop, previous.len()..responders.len())? {
+                        previous.push(response.data().address);
+                    }
+                }
+            }
+
+            flows.push((hop, responders));
+
+            if is_final {
+                break
+            }
+        }
+
+        let mut trace = MultipathTrace {
+            hops: Vec::with_capacity(flows.len()),
+        };
+
+        for (index, (hop, responders)) in flows.iter().enumerate() {
+
+            let mut links: Vec<(IpAddr, IpAddr)> = Vec::new();
+            if let Some((_, previous)) = index.checked_sub(1).map(|index| &(flows[index])) {
+                for link in previous.iter().zip(responders.iter()) {
+                    if let (Some(source), Some(target)) = link {
+                        if !(links.contains(&(*source, *target))) {
+                            links.push((*source, *target));
+                        }
+                    }
+                }
+            }
+
+            trace.hops.push(MultipathHop {
+
+                responders: distinct(responders),
+                flows: responders.len(),
+
+                hops: *hop,
+                links,
+            });
+        }
+
+        Ok(trace)
+    }
+}
+
+fn distinct(responders: &[Option<IpAddr>]) -> Vec<IpAddr> {
+    let mut addresses: Vec<IpAddr> = Vec::new();
+
+    for address in responders.iter().flatten() {
+        if !(addresses.contains(address)) {
+            addresses.push(*address);
+        }
+    }
+
+    addresses
+}
+
+/// Number of flows needed to rule out more than given number of responders,
+/// that is to see all of one more equally likely responder with the given
+/// confidence.
+fn stopping_point(responders: usize, confidence: f64) -> usize {
+    let hypothesis = (responders + 1) as i32;
+
+    // Probability that some responder was never seen, by inclusion-exclusion
+    // over the responders missed.
+    let missing = |flows: i32| -> f64 {
+        let mut binomial = 1.0;
+        let mut sum = 0.0;
+
+        for missed in 1..hypothesis {
+            binomial *= (hypothesis - missed + 1) as f64 / missed as f64;
+
+            let term = binomial * ((hypothesis - missed) as f64 / hypothesis as f64).powi(flows);
+            sum += if missed % 2 == 1 { term } else { -(term) };
+        }
+
+        sum
+    };
+
+    let mut flows = 1;
+    while missing(flows) > (1.0 - confidence) {
+        flows += 1;
+    }
+
+    flows as usize
+}
+
+#[cfg(test)]
+mod test {
+    use super::{stopping_point};
+
+    #[test]
+    fn stopping_points() {
+        let points: Vec<usize> = (1..=8).map(|responders| {
+            stopping_point(responders, 0.95)
+        }).collect();
+
+        assert_eq!(points, vec![6, 11, 16, 21, 27, 33, 38, 44]);
+    }
+}
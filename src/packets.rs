@@ -10,19 +10,24 @@ use std::{
     io::{
 
         Cursor, 
+        Write,
         Read,
     }, 
     
     net::{
 
         SocketAddr,
+        Ipv6Addr,
         Ipv4Addr, 
+        IpAddr,
     },
 };
 
 use crate::{
 
     UnreachableCodeV6,
+    EkkoHeader,
+    EkkoPattern,
     UnreachableCodeV4,
     Unreachable,
     Redirect,
@@ -37,25 +42,102 @@ use byteorder::{
     BigEndian, 
 };
 
+/// Ones' complement sum folded into 16 bits.
+fn fold(mut sum: u32) -> u16 {
+    while sum >> 16 != 0 {
+        sum = (sum >> 16) + (sum & 0xFFFF);
+    }
+
+    sum as u16
+}
+
+/// Ones' complement checksum of data, given a pseudo header of source and
+/// destination addresses along with the protocol it is summed over too, as
+/// is done for ICMPv6 and segments.
+pub(crate) fn checksum(data: &[u8], pseudo: Option<(IpAddr, IpAddr, u8)>) -> u16 {
+    let mut sum: u32 = data.chunks(2).map(|chunk| match *chunk {
+
+        [ .. , a, b ] => u16::from_be_bytes([a, b]) as u32,
+        [ .. , a ] => u16::from_be_bytes([a, 0]) as u32,
+        [ .. ] => 0_u32,
+
+    }).sum();
+
+    if let Some((source, destination, protocol)) = pseudo {
+        for address in [source, destination] {
+            sum += match address {
+                IpAddr::V4(address) => address.octets().chunks(2).map(|chunk| {
+                    u16::from_be_bytes([chunk[0], chunk[1]]) as u32
+                }).sum::<u32>(),
+
+                IpAddr::V6(address) => address.segments().iter().map(|segment| {
+                    *segment as u32
+                }).sum::<u32>(),
+            };
+        }
+
+        sum += data.len() as u32;
+        sum += protocol as u32;
+    }
+
+    !(fold(sum))
+}
+
+/// Value which, added to data summing up to given checksum, turns the checksum
+/// into the value of flow.
+fn compensate(checksum: u16, flow: u16) -> u16 {
+    fold((!flow as u32) + (checksum as u32))
+}
+
+/// Payload of requests unless settings say otherwise.
+pub(crate) const PAYLOAD: &[u8] = b"Ekko, ekko, ekko ..";
+
+/// Payload of given length and pattern, as long as it fits in a single request
+/// to target. The length field of the internet protocol header limits it to
+/// 65535 octets in total.
+pub(crate) fn payload(target: IpAddr, flow: Option<u16>, settings: (usize, &EkkoPattern)) -> Result<Vec<u8>, EkkoError> {
+    let (length, pattern) = settings;
+
+    let limit = match target {
+        IpAddr::V4(_) => 65535 - 20 - 8,
+        IpAddr::V6(_) => 65535 - 8,
+    } - match flow {
+        Some(_) => 2,
+        None => 0,
+    };
+
+    match length {
+        length if length > limit => Err(EkkoError::RequestPayloadLength(length, limit)),
+        length => Ok(pattern.fill(length)),
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
 pub(crate) enum EkkoPacket<'a> {
     V4(&'a [u8]),
     V6(&'a [u8]),
+    /// Datagram header and payload, only ever seen quoted in error messages.
+    Udp(&'a [u8]),
+    /// Segment header, either a request of ours or the reply of a target.
+    Tcp(&'a [u8]),
 }
 
 impl<'a> EkkoPacket<'a> {
-    pub fn new(buf: &'a mut [u8], pkt: (u16, u16), net: (SocketAddr, SocketAddr)) -> Result<EkkoPacket, EkkoError> {
+    /// Build an echo request, given a flow the payload is adjusted so the
+    /// checksum stays at that value for any identifier and sequence. The
+    /// checksum of ICMPv6 is finalized by the kernel, it still stays the same
+    /// for all requests of a flow but only matches the given value when the
+    /// source address is known.
+    pub fn new(buf: &'a mut [u8], pkt: (u16, u16), flow: Option<u16>, payload: &[u8], net: (SocketAddr, SocketAddr)) -> Result<EkkoPacket<'a>, EkkoError> {
         match net {
 
             (SocketAddr::V4(_), SocketAddr::V4(_)) => {
-                EkkoPacket::new_ipv4(&mut buf[..], pkt)
+                EkkoPacket::new_ipv4(&mut buf[..], pkt, flow, payload)
             },
 
             (SocketAddr::V6(src), SocketAddr::V6(dst)) => {
-                let src_ip = src.ip();
-                let dst_ip = dst.ip();
-
-                EkkoPacket::new_ipv6(&mut buf[..], pkt, {
-                    (src_ip.segments(), dst_ip.segments())
+                EkkoPacket::new_ipv6(&mut buf[..], pkt, flow, payload, {
+                    (*src.ip(), *dst.ip())
                 })
             },
 
@@ -65,25 +147,9 @@ impl<'a> EkkoPacket<'a> {
         }
     }
 
-    fn new_ipv4(buf: &'a mut [u8], pkt: (u16, u16)) -> Result<EkkoPacket, EkkoError> {
+    fn new_ipv4(buf: &'a mut [u8], pkt: (u16, u16), flow: Option<u16>, payload: &[u8]) -> Result<EkkoPacket<'a>, EkkoError> {
         let (idf, seq) = pkt;
 
-        fn checksum_v4(data: &[u8]) -> u16 {
-            let mut sum: u32 = data.chunks(2).map(|chunk| match chunk {
-                
-                &[ .. , a, b ] => u16::from_be_bytes([a, b]) as u32,
-                &[ .. , a ] => u16::from_be_bytes([a, 0]) as u32,
-                &[ .. ] => 0 as u32,
-                
-            }).sum();
-            
-            while sum >> 16 != 0 {
-                sum = (sum >> 16) + (sum & 0xFFFF);
-            }
-            
-            !(sum as u16)
-        }
-        
         let mut cursor = Cursor::new(buf);
 
         cursor.write_u8(8).map_err(|e| { 
@@ -106,57 +172,40 @@ impl<'a> EkkoPacket<'a> {
             EkkoError::RequestWriteIcmpv4Field("sequence", e.to_string())
         })?;
 
-        for data in ("Ekko, ekko, ekko ..").chars() {
-            cursor.write_u8(data as u8).map_err(|e| {
-                EkkoError::RequestWriteIcmpv6Payload(e.to_string())
+        if flow.is_some() {
+            cursor.write_u16::<BigEndian>(0).map_err(|e| {
+                EkkoError::RequestWriteIcmpv4Field("flow placeholder", e.to_string())
             })?;
         }
 
-        let length = cursor.position();
+        cursor.write_all(payload).map_err(|e| {
+            EkkoError::RequestWriteIcmpv4Payload(e.to_string())
+        })?;
+
+        let length = cursor.position() as usize;
+
+        if let Some(flow) = flow {
+            let sum = checksum(&(cursor.get_ref()[..length]), None);
+
+            cursor.set_position(8);
+            cursor.write_u16::<BigEndian>(compensate(sum, flow)).map_err(|e| {
+                EkkoError::RequestWriteIcmpv4Field("flow", e.to_string())
+            })?;
+        }
 
         cursor.set_position(2);
-        cursor.write_u16::<BigEndian>(checksum_v4(cursor.get_ref())).map_err(|e| {
+        cursor.write_u16::<BigEndian>(checksum(&(cursor.get_ref()[..length]), None)).map_err(|e| {
             EkkoError::RequestWriteIcmpv4Field("checksum", e.to_string())
         })?;
         
         Ok(EkkoPacket::V4({
-            &(cursor.into_inner()[..({
-                length as usize
-            })])
+            &(cursor.into_inner()[..length])
         }))
     }
     
-    fn new_ipv6<'b>(buf: &'a mut [u8], pkt: (u16, u16), net: ([u16; 8], [u16; 8])) -> Result<EkkoPacket<'a>, EkkoError> {
+    fn new_ipv6(buf: &'a mut [u8], pkt: (u16, u16), flow: Option<u16>, payload: &[u8], net: (Ipv6Addr, Ipv6Addr)) -> Result<EkkoPacket<'a>, EkkoError> {
         let (idf, seq) = pkt;
-
-        fn checksum_v6(data: &[u8], net: ([u16; 8], [u16; 8])) -> u16 {
-            let (src, dst) = net;
-
-            fn sum_segments(segments: [u16; 8]) -> u32 {
-                segments.iter().fold(0, |n, x| {
-                    n + (x.clone() as u32)
-                })
-            }
-            
-            let mut sum: u32 = data.chunks(2).map(|chunk| match chunk {
-                
-                &[ .. , a, b ] => u16::from_be_bytes([a, b]) as u32,
-                &[ .. , a ] => u16::from_be_bytes([a, 0]) as u32,
-                &[ .. ] => 0 as u32,
-                
-            }).sum();
-            
-            sum += sum_segments(src);
-            sum += sum_segments(dst);
-            sum += (data.len() + 16) as u32;
-            sum += 58;
-            
-            while sum >> 16 != 0 {
-                sum = (sum >> 16) + (sum & 0xFFFF);
-            }
-            
-            !(sum as u16)
-        }
+        let pseudo = Some((IpAddr::V6(net.0), IpAddr::V6(net.1), 58));
 
         let mut cursor = Cursor::new(buf);
 
@@ -180,36 +229,187 @@ impl<'a> EkkoPacket<'a> {
             EkkoError::RequestWriteIcmpv6Field("sequence", e.to_string())
         })?;
 
-        for data in ("Ekko, ekko, ekko ..").chars() {
-            cursor.write_u8(data as u8).map_err(|e| {
-                EkkoError::RequestWriteIcmpv6Payload(e.to_string())
+        if flow.is_some() {
+            cursor.write_u16::<BigEndian>(0).map_err(|e| {
+                EkkoError::RequestWriteIcmpv6Field("flow placeholder", e.to_string())
             })?;
         }
 
-        let length = cursor.position();
+        cursor.write_all(payload).map_err(|e| {
+            EkkoError::RequestWriteIcmpv6Payload(e.to_string())
+        })?;
+
+        let length = cursor.position() as usize;
+
+        if let Some(flow) = flow {
+            let sum = checksum(&(cursor.get_ref()[..length]), pseudo);
+
+            cursor.set_position(8);
+            cursor.write_u16::<BigEndian>(compensate(sum, flow)).map_err(|e| {
+                EkkoError::RequestWriteIcmpv6Field("flow", e.to_string())
+            })?;
+        }
 
         cursor.set_position(2);
-        cursor.write_u16::<BigEndian>(checksum_v6(cursor.get_ref(), net)).map_err(|e| {
+        cursor.write_u16::<BigEndian>(checksum(&(cursor.get_ref()[..length]), pseudo)).map_err(|e| {
             EkkoError::RequestWriteIcmpv6Field("checksum", e.to_string())
         })?;
         
         Ok(EkkoPacket::V6({
-            &(cursor.into_inner()[..({
-                length as usize
-            })])
+            &(cursor.into_inner()[..length])
         }))
     }
     
+    /// Build a connection request (SYN) from identifier as source port to
+    /// given port, the sequence goes into the low half of the sequence number.
+    /// The checksum covers the source address, so it has to be the one the
+    /// kernel picks for the target.
+    pub fn new_tcp(buf: &'a mut [u8], pkt: (u16, u16), port: u16, net: (IpAddr, IpAddr)) -> Result<EkkoPacket<'a>, EkkoError> {
+        let (idf, seq) = pkt;
+
+        let mut cursor = Cursor::new(buf);
+
+        cursor.write_u16::<BigEndian>(idf).map_err(|e| {
+            EkkoError::RequestWriteTcpField("source port", e.to_string())
+        })?;
+
+        cursor.write_u16::<BigEndian>(port).map_err(|e| {
+            EkkoError::RequestWriteTcpField("destination port", e.to_string())
+        })?;
+
+        cursor.write_u32::<BigEndian>(((idf as u32) << 16) | (seq as u32)).map_err(|e| {
+            EkkoError::RequestWriteTcpField("sequence number", e.to_string())
+        })?;
+
+        cursor.write_u32::<BigEndian>(0).map_err(|e| {
+            EkkoError::RequestWriteTcpField("acknowledgement number", e.to_string())
+        })?;
+
+        // Header of five words, synchronize flag only.
+        cursor.write_u16::<BigEndian>(0x5002).map_err(|e| {
+            EkkoError::RequestWriteTcpField("flags", e.to_string())
+        })?;
+
+        cursor.write_u16::<BigEndian>(1024).map_err(|e| {
+            EkkoError::RequestWriteTcpField("window", e.to_string())
+        })?;
+
+        cursor.write_u16::<BigEndian>(0).map_err(|e| {
+            EkkoError::RequestWriteTcpField("checksum placeholder", e.to_string())
+        })?;
+
+        cursor.write_u16::<BigEndian>(0).map_err(|e| {
+            EkkoError::RequestWriteTcpField("urgent pointer", e.to_string())
+        })?;
+
+        let length = cursor.position() as usize;
+
+        cursor.set_position(16);
+        cursor.write_u16::<BigEndian>(checksum(&(cursor.get_ref()[..length]), Some((net.0, net.1, 6)))).map_err(|e| {
+            EkkoError::RequestWriteTcpField("checksum", e.to_string())
+        })?;
+
+        Ok(EkkoPacket::Tcp({
+            &(cursor.into_inner()[..length])
+        }))
+    }
+
+    /// Build a control message of any type, the rest of the header follows
+    /// the checksum and precedes the payload. The checksum of ICMPv6 covers
+    /// the source and destination addresses, given as a pseudo header.
+    pub fn new_message(buf: &'a mut [u8], header: (u8, u8, [u8; 4]), payload: &[u8], pseudo: Option<(Ipv6Addr, Ipv6Addr)>) -> Result<EkkoPacket<'a>, EkkoError> {
+        let (kind, code, rest) = header;
+
+        let field = |field: &'static str, e: std::io::Error| match pseudo {
+            Some(_) => EkkoError::RequestWriteIcmpv6Field(field, e.to_string()),
+            None => EkkoError::RequestWriteIcmpv4Field(field, e.to_string()),
+        };
+
+        let mut cursor = Cursor::new(buf);
+
+        cursor.write_u8(kind).map_err(|e| {
+            field("type", e)
+        })?;
+
+        cursor.write_u8(code).map_err(|e| {
+            field("code", e)
+        })?;
+
+        cursor.write_u16::<BigEndian>(0).map_err(|e| {
+            field("checksum placeholder", e)
+        })?;
+
+        cursor.write_all(&(rest)).map_err(|e| {
+            field("rest of header", e)
+        })?;
+
+        cursor.write_all(payload).map_err(|e| match pseudo {
+            Some(_) => EkkoError::RequestWriteIcmpv6Payload(e.to_string()),
+            None => EkkoError::RequestWriteIcmpv4Payload(e.to_string()),
+        })?;
+
+        let length = cursor.position() as usize;
+        let pseudo = pseudo.map(|(source, destination)| {
+            (IpAddr::V6(source), IpAddr::V6(destination), 58)
+        });
+
+        cursor.set_position(2);
+        cursor.write_u16::<BigEndian>(checksum(&(cursor.get_ref()[..length]), pseudo)).map_err(|e| {
+            field("checksum", e)
+        })?;
+
+        let buf = &(cursor.into_inner()[..length]);
+
+        Ok(match pseudo {
+            Some(_) => EkkoPacket::V6(buf),
+            None => EkkoPacket::V4(buf),
+        })
+    }
+
     pub fn as_slice(&self) -> &'a [u8] {
         match self {
 
             Self::V4(buf) => &(buf[..]),
             Self::V6(buf) => &(buf[..]),
+            Self::Udp(buf) => &(buf[..]),
+            Self::Tcp(buf) => &(buf[..]),
+        }
+    }
+
+    /// Whether this is a request of ours rather than a response to one.
+    pub fn is_request(&self) -> Result<bool, EkkoError> {
+        match self {
+
+            Self::V4(_) | Self::V6(_) => {
+                Ok(self.get_type()? == 128 || self.get_type()? == 8)
+            }
+
+            Self::Udp(_) => Ok(true),
+            Self::Tcp(_) => Ok(!(self.is_acknowledgement()?)),
+        }
+    }
+
+    /// Whether a segment has the acknowledgement flag set, which replies to
+    /// our requests always have.
+    fn is_acknowledgement(&self) -> Result<bool, EkkoError> {
+        match self {
+
+            Self::Tcp(buf) => {
+                let mut cursor = Cursor::new(buf);
+
+                cursor.set_position(13);
+                Ok(cursor.read_u8().map_err(|e| {
+                    EkkoError::ResponseReadField("flags", e.to_string())
+                })? & 0x10 != 0)
+            }
+
+            _ => Ok(false),
         }
     }
 
-    pub fn is_echo_request(&self) -> Result<bool, EkkoError> {
-        Ok(self.get_type()? == 128 || self.get_type()? == 8)
+    /// Whether this is an error message quoting a datagram.
+    pub fn is_datagram_originator(&self) -> bool {
+        matches!(self.get_originator(), Ok(EkkoPacket::Udp(_)))
     }
 
     #[allow(dead_code)]
@@ -228,6 +428,10 @@ impl<'a> EkkoPacket<'a> {
                     EkkoError::ResponseReadField("type", e.to_string())
                 })?)
             }
+
+            Self::Udp(_) | Self::Tcp(_) => Err(EkkoError::ResponseReadField("type", {
+                "not a control message".to_owned()
+            })),
         }
     }
 
@@ -242,6 +446,10 @@ impl<'a> EkkoPacket<'a> {
                     EkkoError::ResponseReadField("code", e.to_string())
                 })?)
             }
+
+            Self::Udp(_) | Self::Tcp(_) => Err(EkkoError::ResponseReadField("code", {
+                "not a control message".to_owned()
+            })),
         }
     }
 
@@ -256,6 +464,24 @@ impl<'a> EkkoPacket<'a> {
                     EkkoError::ResponseReadField("checksum", e.to_string())
                 })?)
             }
+
+            Self::Udp(buf) => {
+                let mut cursor = Cursor::new(buf);
+
+                cursor.set_position(6);
+                Ok(cursor.read_u16::<BigEndian>().map_err(|e| {
+                    EkkoError::ResponseReadField("checksum", e.to_string())
+                })?)
+            }
+
+            Self::Tcp(buf) => {
+                let mut cursor = Cursor::new(buf);
+
+                cursor.set_position(16);
+                Ok(cursor.read_u16::<BigEndian>().map_err(|e| {
+                    EkkoError::ResponseReadField("checksum", e.to_string())
+                })?)
+            }
         }
     }
 
@@ -297,6 +523,29 @@ impl<'a> EkkoPacket<'a> {
                         .get_identifier()
                 }
             }
+
+            Self::Udp(buf) => {
+                let mut cursor = Cursor::new(buf);
+
+                cursor.set_position(0);
+                Ok(cursor.read_u16::<BigEndian>().map_err(|e| {
+                    EkkoError::ResponseReadField("identifier", e.to_string())
+                })?)
+            }
+
+            // Replies are addressed to the port requests came from.
+            Self::Tcp(buf) => {
+                let mut cursor = Cursor::new(buf);
+
+                cursor.set_position(match self.is_acknowledgement()? {
+                    true => 2,
+                    false => 0,
+                });
+
+                Ok(cursor.read_u16::<BigEndian>().map_err(|e| {
+                    EkkoError::ResponseReadField("identifier", e.to_string())
+                })?)
+            }
         }
     }
 
@@ -338,6 +587,37 @@ impl<'a> EkkoPacket<'a> {
                         .get_sequence()
                 }
             }
+
+            Self::Udp(buf) => {
+                let mut cursor = Cursor::new(buf);
+
+                cursor.set_position(2);
+                Ok(cursor.read_u16::<BigEndian>().map_err(|e| {
+                    EkkoError::ResponseReadField("sequence number", e.to_string())
+                })?)
+            }
+
+            // Replies acknowledge the sequence number of the request plus one.
+            Self::Tcp(buf) => {
+                let mut cursor = Cursor::new(buf);
+
+                match self.is_acknowledgement()? {
+
+                    true => {
+                        cursor.set_position(8);
+                        Ok((cursor.read_u32::<BigEndian>().map_err(|e| {
+                            EkkoError::ResponseReadField("acknowledgement number", e.to_string())
+                        })?.wrapping_sub(1)) as u16)
+                    }
+
+                    false => {
+                        cursor.set_position(4);
+                        Ok(cursor.read_u32::<BigEndian>().map_err(|e| {
+                            EkkoError::ResponseReadField("sequence number", e.to_string())
+                        })? as u16)
+                    }
+                }
+            }
         }
     }
 
@@ -356,9 +636,25 @@ impl<'a> EkkoPacket<'a> {
                             EkkoError::ResponseReadField("internet protocol header size", e.to_string())
                         })? & 0x0F) * 4) as usize;
 
-                        Ok(EkkoPacket::V4({
-                            &(buf[(8 + header_octets)..])
-                        }))
+                        cursor.set_position(17);
+                        let protocol = cursor.read_u8().map_err(|e| {
+                            EkkoError::ResponseReadField("internet protocol", e.to_string())
+                        })?;
+
+                        let originator = buf.get((8 + header_octets)..).ok_or_else(|| {
+                            EkkoError::ResponseReadField("originator", {
+                                "truncated originator".to_owned()
+                            })
+                        })?;
+
+                        match protocol {
+                            17 => Ok(EkkoPacket::Udp(originator)),
+                            6 => Ok(EkkoPacket::Tcp(originator)),
+                            1 => Ok(EkkoPacket::V4(originator)),
+                            x => Err(EkkoError::ResponseReadField("originator", {
+                                format!("unexpected protocol: {}", x)
+                            })),
+                        }
                     },
 
                     x => Err({
@@ -372,7 +668,7 @@ impl<'a> EkkoPacket<'a> {
             Self::V6(buf) => {
                 match self.get_type()? {
 
-                    1 | 2 | 3 | 4 => (),
+                    1..=4 => (),
 
                     x => return Err({
                         EkkoError::RequestReadField("originator", {
@@ -381,10 +677,189 @@ impl<'a> EkkoPacket<'a> {
                     })
                 }
 
-                Ok(EkkoPacket::V6({
-                    &(buf[48..])
-                }))
+                let mut cursor = Cursor::new(buf);
+
+                cursor.set_position(14);
+                let mut protocol = cursor.read_u8().map_err(|e| {
+                    EkkoError::ResponseReadField("next header", e.to_string())
+                })?;
+
+                // Large requests are fragmented, which puts a fragment header
+                // between the quoted header and the request.
+                let mut offset = 48;
+                while let 0 | 43 | 44 | 60 = protocol {
+
+                    let header = buf.get(offset..(offset + 2)).ok_or_else(|| {
+                        EkkoError::ResponseReadField("extension header", {
+                            "truncated extension header".to_owned()
+                        })
+                    })?;
+
+                    offset += match protocol {
+                        44 => 8,
+                        _ => ((header[1] as usize) + 1) * 8,
+                    };
+
+                    protocol = header[0];
+                }
+
+                let originator = buf.get(offset..).ok_or_else(|| {
+                    EkkoError::ResponseReadField("originator", {
+                        "truncated originator".to_owned()
+                    })
+                })?;
+
+                match protocol {
+                    17 => Ok(EkkoPacket::Udp(originator)),
+                    6 => Ok(EkkoPacket::Tcp(originator)),
+                    58 => Ok(EkkoPacket::V6(originator)),
+                    x => Err(EkkoError::ResponseReadField("originator", {
+                        format!("unexpected next header: {}", x)
+                    })),
+                }
             }
+
+            Self::Udp(_) | Self::Tcp(_) => Err({
+                EkkoError::RequestReadField("originator", {
+                    "missing originator for datagram or segment".to_owned()
+                })
+            }),
+        }
+    }
+
+    /// Destination address of the quoted request, only present in error messages.
+    pub fn get_destination(&self) -> Result<IpAddr, EkkoError> {
+        match self {
+
+            Self::V4(buf) => {
+                match self.get_type()? {
+
+                    3 | 4 | 5 | 11 | 12 => {
+
+                        let mut cursor = Cursor::new(buf);
+                        let mut octets: [u8; 4] = [0; 4];
+
+                        cursor.set_position(24);
+                        cursor.read_exact(&mut octets).map_err(|e| {
+                            EkkoError::ResponseReadField("destination", e.to_string())
+                        })?;
+
+                        Ok(IpAddr::V4(Ipv4Addr::from(octets)))
+                    },
+
+                    x => Err({
+                        EkkoError::RequestReadField("originator", {
+                            format!("missing originator for type: {}", x)
+                        })
+                    })
+                }
+            }
+
+            Self::V6(buf) => {
+                match self.get_type()? {
+
+                    1..=4 => {
+
+                        let mut cursor = Cursor::new(buf);
+                        let mut octets: [u8; 16] = [0; 16];
+
+                        cursor.set_position(32);
+                        cursor.read_exact(&mut octets).map_err(|e| {
+                            EkkoError::ResponseReadField("destination", e.to_string())
+                        })?;
+
+                        Ok(IpAddr::V6(Ipv6Addr::from(octets)))
+                    },
+
+                    x => Err({
+                        EkkoError::RequestReadField("originator", {
+                            format!("missing originator for type: {}", x)
+                        })
+                    })
+                }
+            }
+
+            Self::Udp(_) | Self::Tcp(_) => Err({
+                EkkoError::RequestReadField("originator", {
+                    "missing originator for datagram or segment".to_owned()
+                })
+            }),
+        }
+    }
+
+    /// Internet protocol header of the quoted request along with the checksum
+    /// of what it carried, only present in error messages.
+    pub fn get_quoted(&self) -> Result<EkkoHeader, EkkoError> {
+
+        // Segments may be quoted without their checksum.
+        let checksum = self.get_originator()?
+            .get_checksum().ok();
+
+        match self {
+
+            Self::V4(buf) => {
+
+                let header = buf.get(8..28).ok_or_else(|| {
+                    EkkoError::ResponseReadField("originator", {
+                        "truncated originator".to_owned()
+                    })
+                })?;
+
+                let header_octets = ((header[0] & 0x0F) as u16) * 4;
+
+                Ok(EkkoHeader {
+
+                    source: IpAddr::V4(Ipv4Addr::new(header[12], header[13], header[14], header[15])),
+                    destination: IpAddr::V4(Ipv4Addr::new(header[16], header[17], header[18], header[19])),
+
+                    ttl: header[8],
+                    tos: header[1],
+
+                    identification: Some(u16::from_be_bytes([header[4], header[5]])),
+                    length: u16::from_be_bytes([header[2], header[3]])
+                        .saturating_sub(header_octets),
+
+                    checksum,
+                })
+            }
+
+            Self::V6(buf) => {
+
+                let header = buf.get(8..48).ok_or_else(|| {
+                    EkkoError::ResponseReadField("originator", {
+                        "truncated originator".to_owned()
+                    })
+                })?;
+
+                let mut source: [u8; 16] = [0; 16];
+                let mut destination: [u8; 16] = [0; 16];
+
+                source.copy_from_slice(&(header[8..24]));
+                destination.copy_from_slice(&(header[24..40]));
+
+                // Traffic class sits between the version and flow label.
+                let class = u16::from_be_bytes([header[0], header[1]]);
+
+                Ok(EkkoHeader {
+
+                    source: IpAddr::V6(Ipv6Addr::from(source)),
+                    destination: IpAddr::V6(Ipv6Addr::from(destination)),
+
+                    ttl: header[7],
+                    tos: ((class >> 4) & 0xFF) as u8,
+
+                    identification: None,
+                    length: u16::from_be_bytes([header[4], header[5]]),
+
+                    checksum,
+                })
+            }
+
+            Self::Udp(_) | Self::Tcp(_) => Err({
+                EkkoError::RequestReadField("originator", {
+                    "missing originator for datagram or segment".to_owned()
+                })
+            }),
         }
     }
 
@@ -419,14 +894,149 @@ impl<'a> EkkoPacket<'a> {
                 }
             }
 
-            Self::V6(_) => {
+            Self::V6(_) | Self::Udp(_) | Self::Tcp(_) => {
+                Err(EkkoError::RequestReadIcmpv6Type("redirect", {
+                    "not a redirect response".to_owned()
+                }))
+            }
+        }
+    }
+
+    pub fn get_packet_too_big(&self) -> Result<u32, EkkoError> {
+        match self {
+
+            Self::V6(buf) => {
                 match self.get_type()? {
 
+                    2 => {
+
+                        let mut cursor = Cursor::new(buf);
+
+                        cursor.set_position(4);
+                        cursor.read_u32::<BigEndian>().map_err(|e| {
+                            EkkoError::ResponseReadField("mtu", e.to_string())
+                        })
+                    }
+
+                    _ => Err(EkkoError::RequestReadIcmpv6Type("packet too big", {
+                        "not a packet too big response".to_owned()
+                    }))
+                }
+            }
+
+            Self::V4(_) | Self::Udp(_) | Self::Tcp(_) => {
+                Err(EkkoError::RequestReadIcmpv4Type("packet too big", {
+                    "not a packet too big response".to_owned()
+                }))
+            }
+        }
+    }
+
+    /// Offset of the problem in the quoted request of a parameter problem.
+    pub fn get_parameter_problem(&self) -> Result<u32, EkkoError> {
+        match self {
+
+            Self::V4(buf) => {
+                match self.get_type()? {
+
+                    12 => {
+
+                        let mut cursor = Cursor::new(buf);
+
+                        cursor.set_position(4);
+                        Ok(cursor.read_u8().map_err(|e| {
+                            EkkoError::ResponseReadField("pointer", e.to_string())
+                        })? as u32)
+                    }
+
+                    _ => Err(EkkoError::RequestReadIcmpv4Type("parameter problem", {
+                        "not a parameter problem response".to_owned()
+                    }))
+                }
+            }
+
+            Self::V6(buf) => {
+                match self.get_type()? {
+
+                    4 => {
+
+                        let mut cursor = Cursor::new(buf);
+
+                        cursor.set_position(4);
+                        cursor.read_u32::<BigEndian>().map_err(|e| {
+                            EkkoError::ResponseReadField("pointer", e.to_string())
+                        })
+                    }
+
+                    _ => Err(EkkoError::RequestReadIcmpv6Type("parameter problem", {
+                        "not a parameter problem response".to_owned()
+                    }))
+                }
+            }
+
+            Self::Udp(_) | Self::Tcp(_) => Err(EkkoError::ResponseReadField("type", {
+                "not a control message".to_owned()
+            })),
+        }
+    }
+
+    /// Better first hop of a redirect, along with the destination redirected
+    /// which ICMPv4 only names in the quoted request.
+    pub fn get_gateway(&self) -> Result<(IpAddr, Option<IpAddr>), EkkoError> {
+        match self {
+
+            Self::V4(buf) => {
+                match self.get_type()? {
+
+                    5 => {
+
+                        let mut cursor = Cursor::new(buf);
+                        let mut octets: [u8; 4] = [0; 4];
+
+                        cursor.set_position(4);
+                        cursor.read_exact(&mut octets).map_err(|e| {
+                            EkkoError::ResponseReadField("gateway", e.to_string())
+                        })?;
+
+                        Ok((IpAddr::V4(Ipv4Addr::from(octets)), None))
+                    }
+
+                    _ => Err(EkkoError::RequestReadIcmpv4Type("redirect", {
+                        "not redirect response".to_owned()
+                    }))
+                }
+            }
+
+            Self::V6(buf) => {
+                match self.get_type()? {
+
+                    137 => {
+
+                        let mut cursor = Cursor::new(buf);
+                        let mut gateway: [u8; 16] = [0; 16];
+                        let mut destination: [u8; 16] = [0; 16];
+
+                        cursor.set_position(8);
+                        cursor.read_exact(&mut gateway).map_err(|e| {
+                            EkkoError::ResponseReadField("gateway", e.to_string())
+                        })?;
+
+                        cursor.read_exact(&mut destination).map_err(|e| {
+                            EkkoError::ResponseReadField("destination", e.to_string())
+                        })?;
+
+                        Ok((IpAddr::V6(Ipv6Addr::from(gateway)), Some(IpAddr::V6(Ipv6Addr::from(destination)))))
+                    }
+
                     _ => Err(EkkoError::RequestReadIcmpv6Type("redirect", {
                         "not a redirect response".to_owned()
                     }))
                 }
             }
+
+            Self::Udp(_) | Self::Tcp(_) => Err(EkkoError::ResponseReadField("type", {
+                "not a control message".to_owned()
+            })),
         }
     }
 
@@ -448,7 +1058,7 @@ impl<'a> EkkoPacket<'a> {
                                 
                                 cursor.set_position(6);
                                 cursor.read_u16::<BigEndian>().map_err(|e| {
-                                    EkkoError::ResponseReadField("problem pointer", e.to_string())
+                                    EkkoError::ResponseReadField("next hop mtu", e.to_string())
                                 })?
                             }),
                             5  => UnreachableCodeV4::SourceRouteFailed,
@@ -472,7 +1082,7 @@ impl<'a> EkkoPacket<'a> {
                 }
             }
 
-            Self::V6(_) => {
+            Self::V6(_) | Self::Udp(_) | Self::Tcp(_) => {
                 match self.get_type()? {
 
                     1 => {
@@ -513,8 +1123,37 @@ impl<'a> Debug for EkkoPacket<'a> {
 
 #[cfg(test)]
 mod test {
-    use super::{EkkoPacket};
-    use crate::{EkkoError};
+    use std::{
+
+        net::{
+
+            SocketAddrV6,
+            SocketAddr,
+            Ipv6Addr,
+            IpAddr,
+        },
+
+        time::{
+
+            Duration,
+            Instant,
+        },
+    };
+
+    use super::{
+
+        EkkoPacket,
+        PAYLOAD,
+        payload,
+    };
+
+    use crate::{
+
+        EkkoPattern,
+        EkkoResponse,
+        EkkoHeader,
+        EkkoError,
+    };
 
     #[test]
     fn parse_v4() -> Result<(), EkkoError> {
@@ -536,6 +1175,150 @@ mod test {
         Ok(())
     }
 
+    #[test]
+    fn quoted_header_v4() -> Result<(), EkkoError> {
+        let binary = b"\x0B\x00\xF4\xFF\x00\x00\x00\x00\x45\x60\x00\x2F\x61\x91\x00\x00\x01\x01\x3D\xC6\x00\x00\x00\x00\x08\x08\x08\x08\x08\x00\xF3\x52\x0B\xAD\x00\x01\x45\x6B\x6B\x6F\x2C\x20\x65\x6B\x6B\x6F\x2C\x20\x65\x6B\x6B\x6F\x20\x2E\x2E";
+        let packet = EkkoPacket::V4(binary);
+
+        assert_eq!(packet.get_destination()?, IpAddr::from([8, 8, 8, 8]));
+        assert_eq!(packet.get_quoted()?, EkkoHeader {
+
+            source: IpAddr::from([0, 0, 0, 0]),
+            destination: IpAddr::from([8, 8, 8, 8]),
+
+            ttl: 1,
+            tos: 0x60,
+
+            identification: Some(0x6191),
+            length: 27,
+
+            checksum: Some(0xF352),
+        });
+
+        Ok(())
+    }
+
+    #[test]
+    fn flow_v4() -> Result<(), EkkoError> {
+        let net = (SocketAddr::from(([0, 0, 0, 0], 0)), SocketAddr::from(([8, 8, 8, 8], 0)));
+
+        for sequence in 0..64 {
+            let mut buf: [u8; 128] = [0; 128];
+            let packet = EkkoPacket::new(&mut buf[..], (0xBAD, sequence), Some(0xCAFE), PAYLOAD, net)?;
+
+            let mut sum: u32 = packet.as_slice().chunks(2).map(|chunk| {
+                u16::from_be_bytes([chunk[0], chunk.get(1).copied().unwrap_or(0)]) as u32
+            }).sum();
+
+            while sum >> 16 != 0 {
+                sum = (sum >> 16) + (sum & 0xFFFF);
+            }
+
+            assert_eq!(sum, 0xFFFF);
+            assert_eq!(packet.get_checksum()?, 0xCAFE);
+            assert_eq!(packet.get_identifier()?, 0xBAD);
+            assert_eq!(packet.get_sequence()?, sequence);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn flow_v6() -> Result<(), EkkoError> {
+        let (src, dst) = ([0xFD00, 1, 0, 0, 0, 0, 0, 2], [0xFD00, 3, 0, 0, 0, 0, 0, 2]);
+        let net = (SocketAddr::from((src, 0)), SocketAddr::from((dst, 0)));
+
+        for sequence in 0..64 {
+            let mut buf: [u8; 128] = [0; 128];
+            let packet = EkkoPacket::new(&mut buf[..], (0xBAD, sequence), Some(0xCAFE), PAYLOAD, net)?;
+
+            // Pseudo header of source, target, length and next header.
+            let mut sum: u32 = packet.as_slice().chunks(2).map(|chunk| {
+                u16::from_be_bytes([chunk[0], chunk.get(1).copied().unwrap_or(0)]) as u32
+            }).sum::<u32>() + src.iter().chain(dst.iter()).map(|segment| {
+                *segment as u32
+            }).sum::<u32>() + packet.as_slice().len() as u32 + 58;
+
+            while sum >> 16 != 0 {
+                sum = (sum >> 16) + (sum & 0xFFFF);
+            }
+
+            assert_eq!(sum, 0xFFFF);
+            assert_eq!(packet.get_checksum()?, 0xCAFE);
+            assert_eq!(packet.get_identifier()?, 0xBAD);
+            assert_eq!(packet.get_sequence()?, sequence);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn parse_udp_v4() -> Result<(), EkkoError> {
+        let binary = b"\x03\x03\x00\x00\x00\x00\x00\x00\x45\x00\x00\x2F\x00\x00\x40\x00\x01\x11\x00\x00\x0A\x00\x01\x02\x08\x08\x08\x08\xA3\x69\x82\x9E\x00\x1B\x00\x00\x45\x6B\x6B\x6F\x2C\x20\x65\x6B\x6B\x6F\x2C\x20\x65\x6B\x6B\x6F\x20\x2E\x2E";
+        let packet = EkkoPacket::V4(binary);
+
+        assert_eq!(packet.get_identifier()?, 41833);
+        assert_eq!(packet.get_sequence()?, 33438);
+        assert_eq!(packet.get_destination()?, IpAddr::from([8, 8, 8, 8]));
+        assert!(packet.is_datagram_originator());
+        assert!(packet.get_originator()?.is_request()?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn payload_v4() -> Result<(), EkkoError> {
+        let target = IpAddr::from([8, 8, 8, 8]);
+        let pattern = EkkoPattern::Bytes(vec![0xAB, 0xCD, 0xEF]);
+
+        let data = payload(target, Some(0xCAFE), (65505, &(pattern)))?;
+        assert_eq!(&(data[..7]), &[0xAB, 0xCD, 0xEF, 0xAB, 0xCD, 0xEF, 0xAB]);
+
+        assert!(payload(target, Some(0xCAFE), (65506, &(pattern))).is_err());
+        assert!(payload(target, None, (65508, &(EkkoPattern::Zeros))).is_err());
+
+        let mut buf = vec![0; 10 + data.len()];
+        let packet = EkkoPacket::new(&mut buf[..], (0xBAD, 1), Some(0xCAFE), &(data), {
+            (SocketAddr::from(([0, 0, 0, 0], 0)), SocketAddr::from((target, 0)))
+        })?;
+
+        assert_eq!(packet.as_slice().len(), 65515);
+        assert_eq!(packet.get_checksum()?, 0xCAFE);
+
+        Ok(())
+    }
+
+    #[test]
+    fn tcp() -> Result<(), EkkoError> {
+        let net = (IpAddr::from([10, 0, 1, 2]), IpAddr::from([10, 0, 3, 2]));
+
+        let mut buf: [u8; 128] = [0; 128];
+        let packet = EkkoPacket::new_tcp(&mut buf[..], (0xBAD, 7), 443, net)?;
+
+        let mut sum: u32 = packet.as_slice().chunks(2).map(|chunk| {
+            u16::from_be_bytes([chunk[0], chunk[1]]) as u32
+        }).sum::<u32>() + 0x0A00 + 0x0102 + 0x0A00 + 0x0302 + 6 + 20;
+
+        while sum >> 16 != 0 {
+            sum = (sum >> 16) + (sum & 0xFFFF);
+        }
+
+        assert_eq!(sum, 0xFFFF);
+        assert_eq!(packet.get_identifier()?, 0xBAD);
+        assert_eq!(packet.get_sequence()?, 7);
+        assert!(packet.is_request()?);
+
+        // Reply accepting the connection, acknowledges sequence number plus one.
+        let binary = b"\x01\xBB\x0B\xAD\x00\x00\x00\x00\x0B\xAD\x00\x08\x50\x12\xFF\xFF\x00\x00\x00\x00";
+        let reply = EkkoPacket::Tcp(binary);
+
+        assert_eq!(reply.get_identifier()?, 0xBAD);
+        assert_eq!(reply.get_sequence()?, 7);
+        assert!(!reply.is_request()?);
+
+        Ok(())
+    }
+
     #[test]
     fn parse_v6() -> Result<(), EkkoError> {
         let binary = b"\x03\x00\xB9\x6D\x00\x00\x00\x00\x60\x00\x00\x00\x00\x1B\x3A\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x80\x00\xD5\xD8\x0B\xAD\x00\x01\x45\x6B\x6B\x6F\x2C\x20\x65\x6B\x6B\x6F\x2C\x20\x65\x6B\x6B\x6F\x20\x2E\x2E";
@@ -555,4 +1338,61 @@ mod test {
 
         Ok(())
     }
+
+    #[test]
+    fn packet_too_big_v6() -> Result<(), EkkoError> {
+        let binary = b"\x02\x00\x00\x00\x00\x00\x05\x78\x60\x00\x00\x00\x00\x1B\x3A\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x80\x00\xD5\xD8\x0B\xAD\x00\x01";
+        let packet = EkkoPacket::V6(binary);
+
+        assert_eq!(packet.get_packet_too_big()?, 1400);
+        assert_eq!(packet.get_originator()?.get_identifier()?, 0xBAD);
+
+        let response = EkkoResponse::new((SocketAddr::from(([0xFD00, 0, 0, 0, 0, 0, 0, 1], 0)), 64), {
+            (Instant::now(), Duration::from_millis(1))
+        }, packet)?;
+
+        assert_eq!(response.mtu(), Some(1400));
+
+        Ok(())
+    }
+
+    #[test]
+    fn scoped_v6() -> Result<(), EkkoError> {
+        let binary = b"\x02\x00\x00\x00\x00\x00\x05\x78\x60\x00\x00\x00\x00\x1B\x3A\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x80\x00\xD5\xD8\x0B\xAD\x00\x01";
+        let packet = EkkoPacket::V6(binary);
+
+        // Routers may well respond from their link-local address.
+        let responder = SocketAddrV6::new(Ipv6Addr::new(0xFE80, 0, 0, 0, 0, 0, 0, 1), 0, 0, 3);
+        let response = EkkoResponse::new((SocketAddr::V6(responder), 64), {
+            (Instant::now(), Duration::from_millis(1))
+        }, packet)?;
+
+        assert_eq!(response.data().scope_id, 3);
+        assert_eq!(response.data().socket_address(), Some(SocketAddr::V6(responder)));
+
+        Ok(())
+    }
+
+    #[test]
+    fn quoted_v6() -> Result<(), EkkoError> {
+        let binary = b"\x03\x00\x00\x00\x00\x00\x00\x00\x6B\x80\x00\x00\x00\x1B\x3A\x01\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x80\x00\xD5\xD8\x0B\xAD\x00\x01";
+        let packet = EkkoPacket::V6(binary);
+
+        let quoted = packet.get_quoted()?;
+
+        // Expedited forwarding, as sent.
+        assert_eq!(quoted.tos, 0xB8);
+        assert_eq!(quoted.ttl, 1);
+        assert_eq!(quoted.identification, None);
+        assert_eq!(quoted.length, 27);
+        assert_eq!(quoted.checksum, Some(0xD5D8));
+
+        let response = EkkoResponse::new((SocketAddr::from(([0; 16], 0)), 1), {
+            (Instant::now(), Duration::from_millis(1))
+        }, packet)?;
+
+        assert_eq!(response.data().quoted, Some(quoted));
+
+        Ok(())
+    }
 }
@@ -0,0 +1,557 @@
+use std::{
+
+    collections::{
+
+        VecDeque,
+        HashMap,
+    },
+
+    net::{
+
+        SocketAddr,
+        IpAddr,
+    },
+
+    sync::{Arc},
+
+    time::{
+
+        Duration,
+        Instant,
+    },
+};
+
+use super::{
+
+    error::{EkkoError},
+    packets::{EkkoPacket},
+
+    dispatcher::{
+
+        EkkoSubscription,
+        EkkoDispatcher,
+        EkkoInbox,
+        EkkoRoute,
+    },
+
+    socket::{
+
+        EkkoReceived,
+        EkkoSent,
+    },
+
+    responses::{
+
+        EkkoResponse,
+        EkkoData,
+    },
+};
+
+/// Response to a single request.
+enum EkkoSlot {
+    Waiting,
+    Matched(EkkoResponse),
+    /// Handed out as response, further ones are duplicates.
+    Answered,
+    /// Handed out as lacking, further ones are ignored.
+    Expired,
+}
+
+impl EkkoSlot {
+    fn is_taken(&self) -> bool {
+        matches!(self, EkkoSlot::Answered | EkkoSlot::Expired)
+    }
+}
+
+/// Echo requests in flight, matches incoming packets against them and keeps
+/// the first response for each.
+pub(crate) struct EkkoRequests {
+
+    requests: VecDeque<(Instant, IpAddr, u16, u16, u32)>,
+    responses: VecDeque<EkkoSlot>,
+    transmitted: VecDeque<Option<Duration>>,
+
+    /// Requests matched but not taken, in the order they were matched.
+    matched: VecDeque<usize>,
+    /// Further responses to requests already answered.
+    duplicates: VecDeque<EkkoResponse>,
+    /// Latest request matched.
+    latest: Option<usize>,
+
+    /// Number of requests popped so far, lookups index from the first
+    /// request ever pushed.
+    popped: usize,
+
+    lookup: HashMap<(IpAddr, u16, u16), usize>,
+    keys: HashMap<u32, usize>,
+
+    /// Where packets are delivered, none when they are read by whoever
+    /// holds the requests.
+    subscription: Option<EkkoSubscription>,
+}
+
+impl EkkoRequests {
+
+    pub(crate) fn with_capacity(capacity: usize) -> EkkoRequests {
+        EkkoRequests {
+
+            requests: VecDeque::with_capacity(capacity),
+            responses: VecDeque::with_capacity(capacity),
+            transmitted: VecDeque::with_capacity(capacity),
+
+            matched: VecDeque::new(),
+            duplicates: VecDeque::new(),
+            latest: None,
+
+            popped: 0,
+
+            lookup: HashMap::with_capacity(capacity),
+            keys: HashMap::new(),
+
+            subscription: None,
+        }
+    }
+
+    /// Requests having packets routed to them by a dispatcher.
+    pub(crate) fn subscribed(capacity: usize, subscription: EkkoSubscription) -> EkkoRequests {
+        EkkoRequests {
+
+            subscription: Some(subscription),
+            ..EkkoRequests::with_capacity(capacity)
+        }
+    }
+
+    /// Target, identifier and sequence a packet is matched against, none for
+    /// requests and packets carrying no identifier and sequence. Error
+    /// messages are matched against the destination of the quoted request.
+    pub(crate) fn key(address: IpAddr, packet: &EkkoPacket) -> Result<Option<(IpAddr, u16, u16)>, EkkoError> {
+        if packet.is_request()? {
+            return Ok(None)
+        }
+
+        match (packet.get_identifier(), packet.get_sequence()) {
+            (Ok(identifier), Ok(sequence)) => Ok(Some((packet.get_destination().unwrap_or(address), identifier, sequence))),
+            _ => Ok(None),
+        }
+    }
+
+    /// Dispatcher requests to target are routed by, along with the inbox
+    /// they are delivered to.
+    fn dispatcher(&self, target: IpAddr) -> Option<(&EkkoDispatcher, &Arc<EkkoInbox>)> {
+        let subscription = self.subscription.as_ref()?;
+        Some((subscription.dispatcher(target)?, &(subscription.inbox)))
+    }
+
+    pub(crate) fn push(&mut self, timepoint: Instant, target: IpAddr, sent: EkkoSent, hops: u32) {
+        let EkkoSent { identifier, sequence, key, .. } = sent;
+        let index = self.popped + self.requests.len();
+
+        // Keys are reused once sequences wrap around, the request sent last
+        // is the one still waiting.
+        self.lookup.insert((target, identifier, sequence), index);
+
+        if let Some(key) = key {
+            self.keys.insert(key, index);
+        }
+
+        // Send timestamps have to be delivered before the responses to them,
+        // in case both arrived already.
+        if let Some((dispatcher, inbox)) = self.dispatcher(target) {
+            if let Some(key) = key {
+                dispatcher.register(EkkoRoute::Transmitted(key), inbox, timepoint);
+            }
+
+            dispatcher.register(EkkoRoute::Request((target, identifier, sequence)), inbox, timepoint);
+        }
+
+        self.requests.push_back((timepoint, target, identifier, sequence, hops));
+        self.responses.push_back(EkkoSlot::Waiting);
+        self.transmitted.push_back(None);
+    }
+
+    /// Match packet against outstanding requests by their key, packets that
+    /// match nothing or carry no key are ignored. Send timestamps are matched
+    /// by their key.
+    pub(crate) fn receive(&mut self, received: EkkoReceived) -> Result<(), EkkoError> {
+        let (address, packet, timestamp, ttl, rebuilt) = match received {
+
+            EkkoReceived::Response(address, packet, timestamp, ttl, rebuilt) => (address, packet, timestamp, ttl, rebuilt),
+            EkkoReceived::Transmitted(key, timestamp) => {
+
+                if let Some(index) = self.keys.get(&(key)) {
+                    self.transmitted[*index - self.popped] = Some(timestamp);
+                }
+
+                return Ok(())
+            },
+        };
+
+        let key = match EkkoRequests::key(address.ip(), &(packet))? {
+            Some(key) => key,
+            None => return Ok(()),
+        };
+
+        let index = match self.lookup.get(&(key)) {
+            Some(index) => *index,
+            None => return Ok(()),
+        };
+
+        let (timepoint, _, _, _, hops) = self.requests[index - self.popped];
+
+        let time = (timepoint, timepoint.elapsed());
+        let net = (address, hops);
+
+        match &mut (self.responses[index - self.popped]) {
+
+            EkkoSlot::Waiting => {
+
+                let mut response = EkkoRequests::response(net, time, (packet, ttl, rebuilt))?;
+
+                if let (Some(sent), Some(received)) = (self.transmitted[index - self.popped], timestamp) {
+                    response.data_mut().kernel_elapsed = received.checked_sub(sent);
+                }
+
+                // A later request was answered first.
+                response.data_mut().reordered = self.latest.map(|latest| latest > index)
+                    .unwrap_or(false);
+
+                self.latest = self.latest.max(Some(index));
+
+                self.responses[index - self.popped] = EkkoSlot::Matched(response);
+                self.matched.push_back(index);
+            },
+
+            EkkoSlot::Matched(response) => {
+                response.data_mut().duplicates += 1;
+            },
+
+            EkkoSlot::Answered => {
+
+                let response = EkkoRequests::response(net, time, (packet, ttl, rebuilt))?;
+                self.duplicates.push_back(EkkoResponse::Duplicate({
+                    response.data().clone()
+                }));
+            },
+
+            EkkoSlot::Expired => (),
+        }
+
+        Ok(())
+    }
+
+    /// Response to packet along with the time to live it arrived with, error
+    /// messages rebuilt from the error queue lack most of the quoted header.
+    fn response(net: (SocketAddr, u32), time: (Instant, Duration), packet: (EkkoPacket, Option<u8>, bool)) -> Result<EkkoResponse, EkkoError> {
+        let (packet, ttl, rebuilt) = packet;
+
+        let mut response = EkkoResponse::new(net, time, packet)?;
+        response.data_mut().received_ttl = ttl;
+
+        if rebuilt {
+            response.data_mut().quoted = None;
+        }
+
+        Ok(response)
+    }
+
+    pub(crate) fn is_complete(&self) -> bool {
+        self.responses.iter().all(|response| {
+            !(matches!(response, EkkoSlot::Waiting))
+        })
+    }
+
+    /// Duplicate as soon as one arrives, otherwise response to the oldest
+    /// request not yet taken, once it has one or has waited for given
+    /// timeout, in which case it is marked as lacking. Later responses to it
+    /// are ignored from then on.
+    pub(crate) fn pop(&mut self, timeout: Duration) -> Option<EkkoResponse> {
+        self.trim(timeout);
+
+        if let Some(duplicate) = self.duplicates.pop_front() {
+            return Some(duplicate)
+        }
+
+        let index = self.responses.iter().position(|response| !(response.is_taken()))?;
+
+        match (self.requests[index], &(self.responses[index])) {
+            (_, EkkoSlot::Matched(_)) => (),
+            ((timepoint, ..), EkkoSlot::Waiting) if timepoint.elapsed() >= timeout => (),
+            _ => return None,
+        }
+
+        Some(self.take(index + self.popped))
+    }
+
+    /// Response to any request, duplicates and those matched first and in
+    /// the order they arrived, then those that have waited for given timeout
+    /// marked as lacking. Later responses to them are ignored from then on.
+    pub(crate) fn next(&mut self, timeout: Duration) -> Option<EkkoResponse> {
+        self.trim(timeout);
+
+        if let Some(duplicate) = self.duplicates.pop_front() {
+            return Some(duplicate)
+        }
+
+        let index = self.matched.pop_front().or_else(|| {
+            self.requests.iter().zip(self.responses.iter()).position(|((timepoint, ..), response)| {
+                matches!(response, EkkoSlot::Waiting) && timepoint.elapsed() >= timeout
+            }).map(|index| index + self.popped)
+        })?;
+
+        Some(self.take(index))
+    }
+
+    /// Instant the earliest waiting request will have waited for given
+    /// timeout, none when no request is waiting.
+    pub(crate) fn deadline(&self, timeout: Duration) -> Option<Instant> {
+        self.requests.iter().zip(self.responses.iter()).find(|(_, response)| {
+            matches!(response, EkkoSlot::Waiting)
+        }).map(|((timepoint, ..), _)| *timepoint + timeout)
+    }
+
+    /// Responses in the order requests were made, requests without a
+    /// response are marked as lacking and those taken are left out.
+    pub(crate) fn finish(self) -> Vec<EkkoResponse> {
+        self.requests.into_iter().zip(self.responses).filter_map(|(request, response)| {
+            match response {
+                EkkoSlot::Waiting => Some(EkkoRequests::lacking(request)),
+                EkkoSlot::Matched(response) => Some(response),
+                EkkoSlot::Answered | EkkoSlot::Expired => None,
+            }
+        }).collect()
+    }
+
+    fn take(&mut self, index: usize) -> EkkoResponse {
+        let request = self.requests[index - self.popped];
+        let slot = &mut (self.responses[index - self.popped]);
+
+        self.matched.retain(|matched| *matched != index);
+
+        match std::mem::replace(slot, EkkoSlot::Expired) {
+
+            EkkoSlot::Matched(response) => {
+                *slot = EkkoSlot::Answered;
+                response
+            },
+
+            _ => EkkoRequests::lacking(request),
+        }
+    }
+
+    /// Forget requests at the front that were taken and have waited for
+    /// given timeout, duplicates arriving later go unnoticed.
+    fn trim(&mut self, timeout: Duration) {
+        while let (Some((timepoint, ..)), Some(response)) = (self.requests.front(), self.responses.front()) {
+
+            if !(response.is_taken()) || timepoint.elapsed() < timeout {
+                break
+            }
+
+            self.responses.pop_front();
+            self.transmitted.pop_front();
+
+            let (_, target, identifier, sequence, _) = match self.requests.pop_front() {
+                Some(request) => request,
+                None => break,
+            };
+
+            let (index, key) = (self.popped, (target, identifier, sequence));
+
+            self.popped += 1;
+
+            if self.lookup.get(&(key)) == Some(&(index)) {
+                self.lookup.remove(&(key));
+            }
+
+            let keys: Vec<u32> = self.keys.iter()
+                .filter(|(_, transmitted)| **transmitted == index)
+                .map(|(key, _)| *key)
+                .collect();
+
+            for key in keys.iter() {
+                self.keys.remove(key);
+            }
+
+            if let Some((dispatcher, inbox)) = self.dispatcher(target) {
+                dispatcher.unregister(EkkoRoute::Request(key), inbox);
+
+                for key in keys {
+                    dispatcher.unregister(EkkoRoute::Transmitted(key), inbox);
+                }
+            }
+        }
+    }
+
+    /// Match packets routed to these requests, waits for some until
+    /// deadline. Requests not subscribed to a dispatcher have none.
+    pub(crate) fn wait(&mut self, deadline: Instant) -> Result<(), EkkoError> {
+        let mail = match &(self.subscription) {
+            Some(EkkoSubscription { inbox, .. }) => inbox.wait(deadline),
+            None => return Ok(()),
+        };
+
+        // Every packet is matched even if one fails to, the first failure
+        // is returned.
+        mail.iter().map(|owned| self.receive(owned.received()))
+            .fold(Ok(()), Result::and)
+    }
+
+    /// Match packets routed to these requests, pending until there are some.
+    #[cfg(all(feature = "async", unix))]
+    pub(crate) fn poll_wait(&mut self, cx: &mut std::task::Context<'_>) -> std::task::Poll<Result<(), EkkoError>> {
+        let mail = match &(self.subscription) {
+
+            Some(EkkoSubscription { inbox, .. }) => match inbox.poll(cx) {
+                std::task::Poll::Ready(mail) => mail,
+                std::task::Poll::Pending => return std::task::Poll::Pending,
+            },
+
+            None => return std::task::Poll::Ready(Ok(())),
+        };
+
+        std::task::Poll::Ready(mail.iter().map(|owned| self.receive(owned.received()))
+            .fold(Ok(()), Result::and))
+    }
+
+    /// Match packets routed to these requests, pending until there are some.
+    #[cfg(all(feature = "async", unix))]
+    pub(crate) async fn wait_async(&mut self) -> Result<(), EkkoError> {
+        std::future::poll_fn(|cx| self.poll_wait(cx)).await
+    }
+
+    fn lacking((timepoint, _, identifier, sequence, hops): (Instant, IpAddr, u16, u16, u32)) -> EkkoResponse {
+        EkkoResponse::Lacking(EkkoData::new((timepoint, timepoint.elapsed()), (identifier, sequence), hops))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+
+        time::{
+
+            Duration,
+            Instant,
+        },
+
+        net::{
+
+            SocketAddr,
+            IpAddr,
+        },
+    };
+
+    use super::{EkkoRequests};
+    use crate::{
+
+        packets::{
+
+            EkkoPacket,
+            PAYLOAD,
+        },
+
+        socket::{
+
+            EkkoReceived,
+            EkkoSent,
+        },
+
+        EkkoResponse,
+        EkkoError,
+    };
+
+    fn reply(requests: &mut EkkoRequests, sequence: u16) -> Result<(), EkkoError> {
+        reply_with_ttl(requests, sequence, None)
+    }
+
+    fn reply_with_ttl(requests: &mut EkkoRequests, sequence: u16, ttl: Option<u8>) -> Result<(), EkkoError> {
+        let net = (SocketAddr::from(([0, 0, 0, 0], 0)), SocketAddr::from(([8, 8, 8, 8], 0)));
+
+        let mut buf: [u8; 128] = [0; 128];
+        let length = EkkoPacket::new(&mut buf[..], (0xBAD, sequence), None, PAYLOAD, net)?
+            .as_slice().len();
+
+        // Echo reply.
+        buf[0] = 0;
+
+        requests.receive(EkkoReceived::Response(SocketAddr::from(([8, 8, 8, 8], 0)), {
+            EkkoPacket::V4(&buf[..length])
+        }, None, ttl, false))
+    }
+
+    #[test]
+    fn duplicates() -> Result<(), EkkoError> {
+        let mut requests = EkkoRequests::with_capacity(2);
+        let timeout = Duration::from_secs(60);
+
+        for sequence in 0..2 {
+            requests.push(Instant::now(), IpAddr::from([8, 8, 8, 8]), EkkoSent {
+                identifier: 0xBAD, sequence, key: None, identification: None, length: 27, checksum: None,
+            }, 64);
+        }
+
+        reply(&mut requests, 1)?;
+        reply(&mut requests, 0)?;
+        reply(&mut requests, 0)?;
+
+        let first = requests.next(timeout).unwrap();
+        let second = requests.next(timeout).unwrap();
+
+        assert_eq!((first.data().sequence, first.data().reordered, first.data().duplicates), (1, false, 0));
+        assert_eq!((second.data().sequence, second.data().reordered, second.data().duplicates), (0, true, 1));
+
+        reply(&mut requests, 1)?;
+
+        match requests.next(timeout) {
+            Some(EkkoResponse::Duplicate(data)) => assert_eq!(data.sequence, 1),
+            response => panic!("expected duplicate, got {:?}", response),
+        }
+
+        assert!(requests.next(timeout).is_none());
+
+        Ok(())
+    }
+
+    #[test]
+    fn reused() -> Result<(), EkkoError> {
+        let mut requests = EkkoRequests::with_capacity(2);
+        let timeout = Duration::from_secs(60);
+
+        let sent = EkkoSent {
+            identifier: 0xBAD, sequence: 0, key: None, identification: None, length: 27, checksum: None,
+        };
+
+        requests.push(Instant::now(), IpAddr::from([8, 8, 8, 8]), sent, 64);
+        reply(&mut requests, 0)?;
+
+        assert!(matches!(requests.next(timeout), Some(EkkoResponse::Destination(_))));
+
+        // Sequence wrapped around while the first request is still kept.
+        requests.push(Instant::now(), IpAddr::from([8, 8, 8, 8]), sent, 64);
+        reply(&mut requests, 0)?;
+
+        assert!(matches!(requests.next(timeout), Some(EkkoResponse::Destination(_))));
+        assert!(requests.next(timeout).is_none());
+
+        Ok(())
+    }
+
+    #[test]
+    fn received_ttl() -> Result<(), EkkoError> {
+        let mut requests = EkkoRequests::with_capacity(1);
+
+        requests.push(Instant::now(), IpAddr::from([8, 8, 8, 8]), EkkoSent {
+            identifier: 0xBAD, sequence: 0, key: None, identification: None, length: 27, checksum: None,
+        }, 64);
+
+        reply_with_ttl(&mut requests, 0, Some(117))?;
+
+        let response = requests.next(Duration::from_secs(60)).unwrap();
+
+        // Sent from an initial time to live of 128, eleven routers back.
+        assert_eq!(response.data().received_ttl, Some(117));
+        assert_eq!(response.data().reverse_hops(), Some(12));
+
+        Ok(())
+    }
+}
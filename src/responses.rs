@@ -4,6 +4,8 @@ use std::{
 
     net::{
         
+        SocketAddrV6,
+        SocketAddr,
         Ipv4Addr,
         IpAddr, 
     },
@@ -73,27 +75,117 @@ pub enum Redirect {
     Unexpected(u8),
 }
 
+/// Internet protocol header of a request, as sent or as quoted back by an
+/// error message, along with the checksum of the echo request, datagram or
+/// segment it carried.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct EkkoHeader {
+    pub source: IpAddr,
+    pub destination: IpAddr,
+    /// Time to live (IPv4) or hop limit (IPv6).
+    pub ttl: u8,
+    /// Type of service (IPv4) or traffic class (IPv6).
+    pub tos: u8,
+    /// Identification, IPv4 only. Unknown for requests as sent, unless sent
+    /// as echo requests on a raw socket along with a header built here.
+    pub identification: Option<u16>,
+    /// Octets following the internet protocol header.
+    pub length: u16,
+    /// Checksum of what the header carried, unknown when left to the kernel.
+    pub checksum: Option<u16>,
+}
+
 #[derive(Clone, Debug, Eq)]
 pub struct EkkoData {
     /// Timepoint for send.
     pub timepoint: Instant, 
-    /// Elapsed time since send.
+    /// Elapsed time since send, as seen from userspace.
     pub elapsed: Duration,
 
     /// Responders address.
     pub address: Option<IpAddr>,
+    /// Index of the interface a link-local responder was reached on, zero
+    /// for any other.
+    pub scope_id: u32,
 
-    /// Echo requests identifier.
+    /// Echo requests identifier, source port of datagrams.
     pub identifier: u16,
-    /// Echo requests sequence.
+    /// Echo requests sequence, destination port of datagrams.
     pub sequence: u16,
     /// Number of hops.
     pub hops: u32,
+    /// Header of the request as quoted back by error messages, routers and
+    /// middleboxes along the way may have rewritten it.
+    pub quoted: Option<EkkoHeader>,
+    /// Time to live (IPv4) or hop limit (IPv6) the response arrived with.
+    pub received_ttl: Option<u8>,
+
+    /// Elapsed time between the kernels send and receive timestamps, when
+    /// enabled on the sender.
+    pub kernel_elapsed: Option<Duration>,
+
+    /// Number of further responses to the same request that arrived before
+    /// this one was returned.
+    pub duplicates: u32,
+    /// Arrived after the response to a request sent later.
+    pub reordered: bool,
+}
+
+impl EkkoData {
+
+    /// Data of a request nothing is known about beyond what was sent, the
+    /// rest is filled in as responses say more.
+    pub(crate) fn new(time: (Instant, Duration), pkt: (u16, u16), hops: u32) -> EkkoData {
+        let (timepoint, elapsed) = time;
+        let (identifier, sequence) = pkt;
+
+        EkkoData {
+
+            timepoint,
+            elapsed,
+
+            address: None,
+            scope_id: 0,
+
+            identifier,
+            sequence,
+            hops,
+            quoted: None,
+            received_ttl: None,
+
+            kernel_elapsed: None,
+
+            duplicates: 0,
+            reordered: false,
+        }
+    }
+
+    /// Estimated number of hops the response took back, assuming the
+    /// responder started from the nearest common initial time to live at or
+    /// above the one received: 64, 128 or 255. Differing from the hops taken
+    /// by the request tells of asymmetric routing.
+    pub fn reverse_hops(&self) -> Option<u32> {
+        let ttl = self.received_ttl? as u32;
+        let initial = [64, 128, 255].iter()
+            .find(|initial| **initial >= ttl)?;
+
+        Some(initial - ttl + 1)
+    }
+
+    /// Responders address along with its scope, telling link-local
+    /// responders on different interfaces apart.
+    pub fn socket_address(&self) -> Option<SocketAddr> {
+        match self.address? {
+            IpAddr::V4(address) => Some(SocketAddr::from((address, 0))),
+            IpAddr::V6(address) => Some(SocketAddr::V6(SocketAddrV6::new(address, 0, 0, self.scope_id))),
+        }
+    }
 }
 
 impl PartialEq for EkkoData {
     fn eq(&self, other: &Self) -> bool {
         self.address.eq(&(other.address)) &&
+        self.scope_id.eq(&(other.scope_id)) &&
         self.hops.eq(&(other.hops))
     }
 }
@@ -113,228 +205,164 @@ impl Ord for EkkoData {
 #[derive(Clone, Debug, PartialEq)]
 pub enum EkkoResponse {
     Unreachable((EkkoData, Unreachable)),
-    PacketTooBig(EkkoData),
+    /// Contains next hops max transmission unit.
+    PacketTooBig((EkkoData, u32)),
     SourceQuench(EkkoData),
     Destination(EkkoData),
     Unexpected((EkkoData, (u8, u8))),
     Redirect((EkkoData, Redirect)),
     Exceeded(EkkoData),
     Lacking(EkkoData),
+    /// Further response to a request already answered, only yielded by
+    /// sessions and streams.
+    Duplicate(EkkoData),
 }
 
 impl EkkoResponse {
-    pub (crate) fn new(net: (IpAddr, u32), time: (Instant, Duration), packet: EkkoPacket) -> Result<Self, EkkoError> {
-        let (timepoint, elapsed) = time;
-        let (address, hops) = net;
 
-        match address {
+    /// Data common to all responses.
+    pub fn data(&self) -> &EkkoData {
+        match self {
+
+            EkkoResponse::Unreachable((data, _)) => data,
+            EkkoResponse::PacketTooBig((data, _)) => data,
+            EkkoResponse::SourceQuench(data) => data,
+            EkkoResponse::Destination(data) => data,
+            EkkoResponse::Unexpected((data, _)) => data,
+            EkkoResponse::Redirect((data, _)) => data,
+            EkkoResponse::Exceeded(data) => data,
+            EkkoResponse::Lacking(data) => data,
+            EkkoResponse::Duplicate(data) => data,
+        }
+    }
 
-            IpAddr::V4(_) => match packet.get_type()? {
+    pub(crate) fn data_mut(&mut self) -> &mut EkkoData {
+        match self {
+
+            EkkoResponse::Unreachable((data, _)) => data,
+            EkkoResponse::PacketTooBig((data, _)) => data,
+            EkkoResponse::SourceQuench(data) => data,
+            EkkoResponse::Destination(data) => data,
+            EkkoResponse::Unexpected((data, _)) => data,
+            EkkoResponse::Redirect((data, _)) => data,
+            EkkoResponse::Exceeded(data) => data,
+            EkkoResponse::Lacking(data) => data,
+            EkkoResponse::Duplicate(data) => data,
+        }
+    }
 
-                3 => {
+    /// Max transmission unit of the next hop, for packet too big (IPv6) and
+    /// fragmentation required (IPv4) responses. Routers predating path MTU
+    /// discovery report zero, which is left out.
+    pub fn mtu(&self) -> Option<u32> {
+        let mtu = match self {
 
-                    Ok(EkkoResponse::Unreachable(({
+            EkkoResponse::Unreachable((_, Unreachable::V4(UnreachableCodeV4::FragmentationRequired(mtu)))) => {
+                Some(*mtu as u32)
+            },
 
-                        EkkoData { 
+            EkkoResponse::PacketTooBig((_, mtu)) => Some(*mtu),
 
-                            timepoint, 
-                            elapsed,
-                            
-                            address: Some(address),
+            _ => None,
+        };
 
-                            identifier: packet.get_identifier()?,
-                            sequence: packet.get_sequence()?,
-                            hops,
-                        }
-                        
-                    }, packet.get_unreachable()?)))
-                }
+        mtu.filter(|mtu| *mtu > 0)
+    }
 
-                4 => {
+    pub (crate) fn new(net: (SocketAddr, u32), time: (Instant, Duration), packet: EkkoPacket) -> Result<Self, EkkoError> {
+        let (responder, hops) = net;
 
-                    Ok(EkkoResponse::SourceQuench({
+        let (address, scope_id) = match responder {
+            SocketAddr::V4(responder) => (IpAddr::V4(*responder.ip()), 0),
+            SocketAddr::V6(responder) => (IpAddr::V6(*responder.ip()), responder.scope_id()),
+        };
 
-                        EkkoData { 
+        let data = EkkoData {
 
-                            timepoint, 
-                            elapsed,
-                            
-                            address: Some(address),
+            address: Some(address),
+            scope_id,
 
-                            identifier: packet.get_identifier()?,
-                            sequence: packet.get_sequence()?,
-                            hops,
-                        }
-                    }))
-                }
+            quoted: packet.get_quoted().ok(),
 
-                5 => {
+            ..EkkoData::new(time, (packet.get_identifier()?, packet.get_sequence()?), hops)
+        };
 
-                    Ok(EkkoResponse::Redirect(({
+        // Only the target replies with segments, whether it accepts or not.
+        if let EkkoPacket::Tcp(_) = packet {
+            return Ok(EkkoResponse::Destination(data))
+        }
 
-                        EkkoData { 
+        match address {
 
-                            timepoint, 
-                            elapsed,
-                            
-                            address: Some(address),
+            IpAddr::V4(_) => match packet.get_type()? {
 
-                            identifier: packet.get_identifier()?,
-                            sequence: packet.get_sequence()?,
-                            hops,
-                        }
+                // Datagrams to closed ports are refused by the target itself.
+                3 if packet.get_code()? == 3 && packet.is_datagram_originator() => {
 
-                    }, packet.get_redirect()?)))
+                    Ok(EkkoResponse::Destination(data))
                 }
 
-                11 => {
-
-                    Ok(EkkoResponse::Exceeded({
-
-                        EkkoData { 
-
-                            timepoint, 
-                            elapsed,
-                            
-                            address: Some(address),
+                3 => {
 
-                            identifier: packet.get_identifier()?,
-                            sequence: packet.get_sequence()?,
-                            hops,
-                        }
-                    }))
+                    Ok(EkkoResponse::Unreachable((data, packet.get_unreachable()?)))
                 }
 
-                0 => {
-
-                    Ok(EkkoResponse::Destination({
+                4 => {
 
-                        EkkoData { 
+                    Ok(EkkoResponse::SourceQuench(data))
+                }
 
-                            timepoint, 
-                            elapsed,
-                            
-                            address: Some(address),
+                5 => {
 
-                            identifier: packet.get_identifier()?,
-                            sequence: packet.get_sequence()?,
-                            hops,
-                        }
-                    }))
+                    Ok(EkkoResponse::Redirect((data, packet.get_redirect()?)))
                 }
 
-                _ => {
+                11 => {
 
-                    Ok(EkkoResponse::Unexpected(({
+                    Ok(EkkoResponse::Exceeded(data))
+                }
 
-                        EkkoData { 
+                0 => {
 
-                            timepoint, 
-                            elapsed,
-                            
-                            address: Some(address),
+                    Ok(EkkoResponse::Destination(data))
+                }
 
-                            identifier: packet.get_identifier()?,
-                            sequence: packet.get_sequence()?,
-                            hops,
-                        }
+                _ => {
 
-                    }, (packet.get_type()?, packet.get_code()?))))
+                    Ok(EkkoResponse::Unexpected((data, (packet.get_type()?, packet.get_code()?))))
                 }
             }
 
             IpAddr::V6(_) => match packet.get_type()? {
 
-                1 => {
+                1 if packet.get_code()? == 4 && packet.is_datagram_originator() => {
 
-                    Ok(EkkoResponse::Unreachable(({
-
-                        EkkoData { 
-
-                            timepoint, 
-                            elapsed,
-                            
-                            address: Some(address),
+                    Ok(EkkoResponse::Destination(data))
+                }
 
-                            identifier: packet.get_identifier()?,
-                            sequence: packet.get_sequence()?,
-                            hops,
-                        }
+                1 => {
 
-                    }, packet.get_unreachable()?)))
+                    Ok(EkkoResponse::Unreachable((data, packet.get_unreachable()?)))
                 }
 
                 2 => {
 
-                    Ok(EkkoResponse::PacketTooBig({
-
-                        EkkoData { 
-
-                            timepoint, 
-                            elapsed,
-                            
-                            address: Some(address),
-
-                            identifier: packet.get_identifier()?,
-                            sequence: packet.get_sequence()?,
-                            hops,
-                        }
-                    }))
+                    Ok(EkkoResponse::PacketTooBig((data, packet.get_packet_too_big()?)))
                 }
 
                 3 => {
 
-                    Ok(EkkoResponse::Exceeded({
-
-                        EkkoData { 
-
-                            timepoint, 
-                            elapsed,
-                            
-                            address: Some(address),
-
-                            identifier: packet.get_identifier()?,
-                            sequence: packet.get_sequence()?,
-                            hops,
-                        }
-                    }))
+                    Ok(EkkoResponse::Exceeded(data))
                 }
 
                 129 => {
 
-                    Ok(EkkoResponse::Destination({
-
-                        EkkoData { 
-
-                            timepoint, 
-                            elapsed,
-                            
-                            address: Some(address),
-
-                            identifier: packet.get_identifier()?,
-                            sequence: packet.get_sequence()?,
-                            hops,
-                        }
-                    }))
+                    Ok(EkkoResponse::Destination(data))
                 }
 
                 _ => {
 
-                    Ok(EkkoResponse::Unexpected(({
-
-                        EkkoData { 
-
-                            timepoint, 
-                            elapsed,
-                            
-                            address: Some(address),
-
-                            identifier: packet.get_identifier()?,
-                            sequence: packet.get_sequence()?,
-                            hops,
-                        }
-
-                    }, (packet.get_type()?, packet.get_code()?))))
+                    Ok(EkkoResponse::Unexpected((data, (packet.get_type()?, packet.get_code()?))))
                 }
             }
         }
@@ -1,57 +1,161 @@
 use std::{
-    
-    ops::{Range}, 
-    io::{Cursor},
+
+    ops::{Range},
 
     time::{
 
-        Duration, 
+        Duration,
         Instant,
     },
 
     net::{
 
-        SocketAddrV6, 
-        SocketAddrV4, 
-        SocketAddr, 
-    }, 
-    
+        SocketAddrV6,
+        SocketAddrV4,
+        SocketAddr,
+    },
+
     net::{
 
-        Ipv6Addr, 
-        Ipv4Addr, 
-        IpAddr, 
-    },   
-};
+        Ipv6Addr,
+        Ipv4Addr,
+        IpAddr,
+    },
+
+    sync::{
 
-use byteorder::{ReadBytesExt};
+        atomic::{
 
-use socket2::{
+            AtomicBool,
+            Ordering,
+        },
 
-    Protocol, 
-    Domain, 
-    Socket, 
-    Type,
+        Arc,
+    },
 };
 
 use super::{
 
-    packets::{EkkoPacket},
+    requests::{EkkoRequests},
+    stream::{EkkoStream},
+
+    dispatcher::{
+
+        EkkoSubscription,
+        EkkoDispatcher,
+    },
+
+    packets::{
+
+        EkkoPacket,
+        PAYLOAD,
+        payload,
+    },
+
     error::{EkkoError},
 
-    responses::{
-        
-        EkkoResponse,
-        EkkoData,
+    socket::{
+
+        interface_index,
+        EkkoSocket,
+        EkkoSent,
+        EkkoProbe,
+        EkkoMode,
     },
+
+    responses::{EkkoResponse},
 };
 
+/// Content of the payload of each request.
+#[derive(Clone, Debug, PartialEq)]
+pub enum EkkoPattern {
+    Zeros,
+    /// Same octet throughout.
+    Byte(u8),
+    /// Random octets, drawn once per call and shared by every request it
+    /// sends.
+    Random,
+    /// Octets repeated until the payload is filled, like the pattern of ping.
+    Bytes(Vec<u8>),
+}
+
+impl EkkoPattern {
+    pub(crate) fn fill(&self, length: usize) -> Vec<u8> {
+        match self {
+
+            EkkoPattern::Zeros => vec![0; length],
+            EkkoPattern::Byte(octet) => vec![*octet; length],
+            EkkoPattern::Random => (0..length).map(|_| {
+                rand::random()
+            }).collect(),
+
+            EkkoPattern::Bytes(octets) if octets.is_empty() => vec![0; length],
+            EkkoPattern::Bytes(octets) => octets.iter()
+                .cycle().take(length)
+                .copied().collect(),
+        }
+    }
+}
+
+/// Explicit congestion notification codepoint, the lower two bits of the
+/// type of service (IPv4) or traffic class (IPv6).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EkkoEcn {
+    /// Not ECN-capable transport.
+    NotEct,
+    /// ECN-capable transport, as marked by L4S senders.
+    Ect1,
+    /// ECN-capable transport, as marked by classic ECN senders.
+    Ect0,
+    /// Congestion experienced.
+    Ce,
+}
+
+impl EkkoEcn {
+
+    /// Codepoint carried by type of service.
+    pub fn from_tos(tos: u8) -> EkkoEcn {
+        match tos & 0x03 {
+            0 => EkkoEcn::NotEct,
+            1 => EkkoEcn::Ect1,
+            2 => EkkoEcn::Ect0,
+            _ => EkkoEcn::Ce,
+        }
+    }
+
+    /// Type of service with its codepoint replaced by this one, keeping the
+    /// DSCP.
+    pub fn apply(self, tos: u8) -> u8 {
+        (tos & 0xFC) | match self {
+            EkkoEcn::NotEct => 0,
+            EkkoEcn::Ect1 => 1,
+            EkkoEcn::Ect0 => 2,
+            EkkoEcn::Ce => 3,
+        }
+    }
+}
+
 /// Take a look at the default implementation.
 pub struct EkkoSettings {
-    
+
     pub identifier: u16,
     pub sequence: u16,
-    
+
+    /// Keeps the checksum of every request at this value, so load balancers
+    /// hashing on it send all requests along the same path (Paris traceroute).
+    /// Datagrams go to a new port for every request and keep no flow, segments
+    /// use the flow as source port.
+    pub flow: Option<u16>,
+
+    /// Octets of payload, at most what fits in a single packet: 65507 for
+    /// IPv4 and 65527 for IPv6, two less with a flow. Segments carry none.
+    pub length: usize,
+    pub pattern: EkkoPattern,
+
+    /// Type of service (IPv4) or traffic class (IPv6) of each request, the
+    /// DSCP in the upper six bits and ECN in the lower two, see [`EkkoEcn`].
+    pub tos: u8,
+
     pub timeout: Duration,
 }
 
@@ -62,6 +166,15 @@ impl Default for EkkoSettings {
             identifier: rand::random(),
             sequence: 0,
 
+            flow: None,
+
+            length: PAYLOAD.len(),
+            pattern: EkkoPattern::Bytes({
+                PAYLOAD.to_vec()
+            }),
+
+            tos: 0,
+
             timeout: {
 
                 Duration::from_millis(1000)
@@ -70,80 +183,341 @@ impl Default for EkkoSettings {
     }
 }
 
+/// Scope of a link-local target.
+#[derive(Clone, Debug)]
+enum EkkoScope {
+    Index(u32),
+    Interface(String),
+}
+
+/// Sender builder, for options beyond those of the constructors on [`Ekko`].
+///
+/// ## Example
+/// ```rust,no_run
+/// use ekko::{
+///
+///     EkkoBuilder,
+///     EkkoError,
+/// };
+///
+/// fn main() -> Result<(), EkkoError> {
+///     let sender = EkkoBuilder::new([8, 8, 8, 8])
+///         .source([192, 168, 1, 2])
+///         .interface("eth1")
+///         .filter(true)
+///         .build()?;
+///
+///     println!("{:?}", sender.send(32)?);
+///
+///     Ok(())
+/// }
+/// ```
+#[derive(Clone, Debug)]
+pub struct EkkoBuilder {
+
+    target: IpAddr,
+
+    source: Option<IpAddr>,
+    interface: Option<String>,
+    vrf: Option<String>,
+
+    scope: Option<EkkoScope>,
+
+    mode: Option<EkkoMode>,
+    probe: EkkoProbe,
+
+    filter: bool,
+}
+
+impl EkkoBuilder {
+
+    /// Builder for a sender with given target address, uses a raw socket
+    /// when permitted and falls back to an unprivileged datagram socket
+    /// otherwise.
+    pub fn new<T: Into<IpAddr>>(target: T) -> EkkoBuilder {
+        EkkoBuilder {
+
+            target: target.into(),
+
+            source: None,
+            interface: None,
+            vrf: None,
+
+            scope: None,
+
+            mode: None,
+            probe: EkkoProbe::Icmp,
+
+            filter: false,
+        }
+    }
+
+    /// Address to send from, has to be of the same family as the target.
+    /// The kernel picks one by the route to the target otherwise.
+    pub fn source<S: Into<IpAddr>>(mut self, source: S) -> EkkoBuilder {
+        self.source = Some(source.into());
+        self
+    }
+
+    /// Interface to send from and receive on, the likes of `ping -I eth1`.
+    /// Only supported on Linux.
+    pub fn interface<I: Into<String>>(mut self, interface: I) -> EkkoBuilder {
+        self.interface = Some(interface.into());
+        self
+    }
+
+    /// VRF to send from and receive in, routes are looked up in its table.
+    /// An interface given as well is bound to instead, it has to be enslaved
+    /// to the VRF. Only supported on Linux.
+    pub fn vrf<V: Into<String>>(mut self, vrf: V) -> EkkoBuilder {
+        self.vrf = Some(vrf.into());
+        self
+    }
+
+    /// Scope of a link-local target by interface index, the likes of
+    /// `fe80::1%2`. Link-local targets are scoped to the interface bound to
+    /// otherwise.
+    pub fn scope_id(mut self, scope_id: u32) -> EkkoBuilder {
+        self.scope = Some(EkkoScope::Index(scope_id));
+        self
+    }
+
+    /// Scope of a link-local target by interface name, the likes of
+    /// `fe80::1%eth1`.
+    pub fn scope<I: Into<String>>(mut self, interface: I) -> EkkoBuilder {
+        self.scope = Some(EkkoScope::Interface(interface.into()));
+        self
+    }
+
+    /// Socket mode to use.
+    pub fn mode(mut self, mode: EkkoMode) -> EkkoBuilder {
+        self.mode = Some(mode);
+        self
+    }
+
+    /// Kind of request to send.
+    pub fn probe(mut self, probe: EkkoProbe) -> EkkoBuilder {
+        self.probe = probe;
+        self
+    }
+
+    /// Have the kernel drop packets not meant for this sender, instead of
+    /// every sender parsing every packet arriving on the host. All requests
+    /// then carry the same identifier, chosen when the sender is built, and
+    /// the sender gets a socket of its own. Only raw sockets for echo
+    /// requests see packets meant for others, so other kinds are left as
+    /// they are. Only supported on Linux.
+    pub fn filter(mut self, filter: bool) -> EkkoBuilder {
+        self.filter = filter;
+        self
+    }
+
+    pub fn build(&self) -> Result<Ekko, EkkoError> {
+        let source = match (self.source, self.target) {
+
+            (None, target) => Ekko::source(target),
+
+            (Some(IpAddr::V4(source)), IpAddr::V4(_)) => SocketAddr::V4(SocketAddrV4::new(source, 0)),
+            (Some(IpAddr::V6(source)), IpAddr::V6(_)) => SocketAddr::V6(SocketAddrV6::new(source, 0, 0, 0)),
+
+            (Some(source), target) => return Err(EkkoError::SocketIpMismatch {
+                src: source.to_string(),
+                dst: target.to_string(),
+            }),
+        };
+
+        // Interfaces enslaved to a VRF route by its table already.
+        let device = self.interface.as_deref()
+            .or(self.vrf.as_deref());
+
+        let target = self.target()?;
+
+        if !(self.filter) {
+            return Ekko::with_dispatcher(target, {
+                EkkoDispatcher::shared(source, device, self.mode, self.probe)?
+            })
+        }
+
+        let socket = EkkoSocket::with_device(source, device, self.mode, self.probe)?;
+        let identifier = rand::random();
+
+        let identifier = match socket.set_filter(identifier)? {
+            true => Some(identifier),
+            false => None,
+        };
+
+        Ok(Ekko {
+            identifier,
+            ..Ekko::with_dispatcher(target, EkkoDispatcher::new(socket)?)?
+        })
+    }
+
+    /// Target along with its scope, if any.
+    fn target(&self) -> Result<SocketAddr, EkkoError> {
+        let target = match self.target {
+            IpAddr::V4(target) => SocketAddrV4::new(target, 0),
+            IpAddr::V6(target) => {
+
+                let index = |interface: &str| interface_index(interface).map_err(|e| {
+                    EkkoError::SocketScope(target.to_string(), format!("interface [{}], {}", interface, e))
+                });
+
+                // Link-local unicast, fe80::/10.
+                let link_local = (target.segments()[0] & 0xFFC0) == 0xFE80;
+                let scope_id = match (&(self.scope), self.interface.as_deref()) {
+                    (Some(EkkoScope::Index(scope_id)), _) => *scope_id,
+                    (Some(EkkoScope::Interface(interface)), _) => index(interface)?,
+                    (None, Some(interface)) if link_local => index(interface)?,
+                    (None, _) => 0,
+                };
+
+                return Ok(SocketAddr::V6(SocketAddrV6::new(target, 0, 0, scope_id)))
+            },
+        };
+
+        match self.scope {
+            Some(_) => Err(EkkoError::SocketScope(target.ip().to_string(), {
+                "only IPv6 targets have a scope".to_owned()
+            })),
+
+            None => Ok(SocketAddr::V4(target)),
+        }
+    }
+}
+
+/// Sender for a single target. Senders are cheap to clone and share their
+/// socket with every other sender of the process using the same options,
+/// a thread of its own reads the socket and hands each response to the
+/// requests waiting for it. Datagram sockets are only shared by clones, as
+/// the kernel gives every request sent on one the same identifier.
 pub struct Ekko {
 
     source_socket_address: SocketAddr,
     target_socket_address: SocketAddr,
 
-    socket: Socket,
+    /// Identifier of every request, when filtered.
+    identifier: Option<u16>,
+    /// Whether responses carry the round trip seen by the kernel.
+    timestamps: AtomicBool,
+
+    dispatcher: Arc<EkkoDispatcher>,
+}
+
+impl Clone for Ekko {
+    fn clone(&self) -> Ekko {
+        Ekko {
+
+            source_socket_address: self.source_socket_address,
+            target_socket_address: self.target_socket_address,
+
+            identifier: self.identifier,
+            timestamps: AtomicBool::new(self.timestamps.load(Ordering::Relaxed)),
+
+            dispatcher: self.dispatcher.clone(),
+        }
+    }
 }
 
 impl Ekko {
 
-    /// Build a sender with given target address.
+    /// Build a sender with given target address, uses a raw socket when
+    /// permitted and falls back to an unprivileged datagram socket otherwise.
     pub fn with_target<T: Into<IpAddr>>(target: T) -> Result<Ekko, EkkoError> {
-        match target.into() {
+        Ekko::with_target_inner(target.into(), None, EkkoProbe::Icmp)
+    }
 
-            IpAddr::V4(target) => {
+    /// Build a sender with given target address and socket mode.
+    pub fn with_target_and_mode<T: Into<IpAddr>>(target: T, mode: EkkoMode) -> Result<Ekko, EkkoError> {
+        Ekko::with_target_inner(target.into(), Some(mode), EkkoProbe::Icmp)
+    }
 
-                let source_address = SocketAddrV4::new(Ipv4Addr::new(0, 0, 0, 0), 0);
-                let socket = Socket::new(Domain::IPV4, Type::RAW, Some(Protocol::ICMPV4)).map_err(|e| {
-                    EkkoError::SocketCreateIcmpv4(e.to_string())
-                })?;
+    /// Build a sender with given target address and kind of request.
+    pub fn with_target_and_probe<T: Into<IpAddr>>(target: T, probe: EkkoProbe) -> Result<Ekko, EkkoError> {
+        Ekko::with_target_inner(target.into(), None, probe)
+    }
 
-                socket.set_nonblocking(true).map_err(|e| {
-                    EkkoError::SocketSetNonBlocking(true, e.to_string())
-                })?;
+    /// Build a sender with given target address along with its scope, for
+    /// link-local targets, see [`EkkoBuilder::scope_id`].
+    pub fn with_scoped_target(target: SocketAddrV6) -> Result<Ekko, EkkoError> {
+        EkkoBuilder::new(*target.ip())
+            .scope_id(target.scope_id())
+            .build()
+    }
 
-                socket.set_recv_buffer_size(256).map_err(|e| {
-                    EkkoError::SocketSetReceiveBufferSize(e.to_string())
-                })?;
+    fn with_target_inner(target: IpAddr, mode: Option<EkkoMode>, probe: EkkoProbe) -> Result<Ekko, EkkoError> {
+        Ekko::with_dispatcher(SocketAddr::new(target, 0), {
+            EkkoDispatcher::shared(Ekko::source(target), None, mode, probe)?
+        })
+    }
 
-                socket.bind(&(source_address.into())).map_err(|e| {
-                    EkkoError::SocketBindIpv4(source_address.to_string(), e.to_string())
-                })?;
-                
-                Ok(Ekko {
+    /// Unspecified source address of the same family as target.
+    fn source(target: IpAddr) -> SocketAddr {
+        match target {
+            IpAddr::V4(_) => SocketAddr::V4(SocketAddrV4::new(Ipv4Addr::new(0, 0, 0, 0), 0)),
+            IpAddr::V6(_) => SocketAddr::V6(SocketAddrV6::new(Ipv6Addr::new(0, 0, 0, 0, 0, 0, 0, 0), 0, 0, 0)),
+        }
+    }
 
-                    source_socket_address: SocketAddr::V4(source_address),
-                    target_socket_address: SocketAddr::V4({
-                        SocketAddrV4::new(target, 0)
-                    }),
+    fn with_dispatcher(target: SocketAddr, dispatcher: Arc<EkkoDispatcher>) -> Result<Ekko, EkkoError> {
+        let socket = dispatcher.socket();
 
-                    socket: socket,
-                })
-            }
+        Ok(Ekko {
 
-            IpAddr::V6(target) => {
+            // Segments and echo requests over IPv6 are checksummed over the
+            // source address, so it has to be known before sending. Echo
+            // requests leave it to the kernel when there is no route yet.
+            source_socket_address: match (socket.probe(), socket.route(target)) {
+                (EkkoProbe::Tcp(_), route) => route?,
+                (_, route) => route.unwrap_or_else(|_| socket.source()),
+            },
+
+            target_socket_address: target,
+
+            identifier: None,
+            timestamps: AtomicBool::new(false),
+
+            dispatcher,
+        })
+    }
+
+    fn socket(&self) -> &EkkoSocket {
+        self.dispatcher.socket()
+    }
+
+    /// Socket mode in use by this sender.
+    pub fn mode(&self) -> EkkoMode {
+        self.socket().mode()
+    }
 
-                let source_address = SocketAddrV6::new(Ipv6Addr::new(0, 0, 0, 0, 0, 0, 0, 0), 0, 0, 0);
-                let socket = Socket::new(Domain::IPV6, Type::RAW, Some(Protocol::ICMPV6)).map_err(|e| {
-                    EkkoError::SocketCreateIcmpv6(e.to_string())
-                })?;
-
-                socket.set_nonblocking(true).map_err(|e| {
-                    EkkoError::SocketSetNonBlocking(true, e.to_string())
-                })?;
-
-                socket.set_recv_buffer_size(512).map_err(|e| {
-                    EkkoError::SocketSetReceiveBufferSize(e.to_string())
-                })?;
-
-                socket.bind(&(source_address.into())).map_err(|e| {
-                    EkkoError::SocketBindIpv6(source_address.to_string(), e.to_string())
-                })?;
-  
-                Ok(Ekko {
-
-                    source_socket_address: SocketAddr::V6(source_address),
-                    target_socket_address: SocketAddr::V6({
-                        SocketAddrV6::new(target, 0, 0, 0)
-                    }),
-
-                    socket: socket,
-                })
-            }
+    /// Kind of request sent by this sender.
+    pub fn probe(&self) -> EkkoProbe {
+        self.socket().probe()
+    }
+
+    /// Have responses to this sender carry the round trip as seen by the
+    /// kernel along with the one seen from userspace. Only supported on
+    /// Linux.
+    pub fn set_timestamps(&self, enable: bool) -> Result<(), EkkoError> {
+        if enable && !(self.socket().timestamps()) {
+            return Err(EkkoError::SocketSetTimestamps({
+                "unsupported by socket".to_owned()
+            }))
         }
+
+        self.timestamps.store(enable, Ordering::Relaxed);
+
+        Ok(())
+    }
+
+    /// Identifier every request carries regardless of settings, when the
+    /// sender was built with a filter and its socket is filtered.
+    pub fn identifier(&self) -> Option<u16> {
+        self.identifier
+    }
+
+    /// Target address of this sender.
+    pub fn target(&self) -> IpAddr {
+        self.target_socket_address.ip()
     }
 
     /// Send an echo request with default settings.
@@ -152,276 +526,220 @@ impl Ekko {
     }
 
     /// Send an echo request with user defined settings.
-    pub fn send_with_settings(&self, hops: u32, EkkoSettings { 
-        timeout, identifier, sequence 
+    pub fn send_with_settings(&self, hops: u32, EkkoSettings {
+        timeout, identifier, sequence, flow, length, pattern, tos
     }: EkkoSettings) -> Result<EkkoResponse, EkkoError> {
 
-        let mut buf: [u8; 256] = {
-            [0; 256]
-        };
+        let payload = payload(self.target_socket_address.ip(), flow, {
+            (length, &(pattern))
+        })?;
+
+        self.inner_reserve(1, payload.len())?;
 
         let timepoint = Instant::now();
+        let mut requests = self.inner_requests(1);
 
-        self.inner_send(hops, {
+        let pkt = self.inner_send(hops, tos, {
             (identifier, sequence)
-        })?;
-
-        let result = loop {
-
-            let identifier = identifier;
-            if let Some((address, packet)) = self.inner_recv(&mut buf)? {
-                if packet.is_echo_request()? {
-                    continue
-                }
+        }, flow, &(payload))?;
 
-                if identifier == packet.get_identifier()? && 
-                   sequence == packet.get_sequence()? {
+        requests.push(timepoint, self.target_socket_address.ip(), pkt, hops);
+        self.inner_wait(&mut requests, timepoint + timeout)?;
 
-                    let time = (timepoint.clone(), timepoint.elapsed());
-                    let net = (address.clone(), hops.clone());
+        Ok(requests.finish().remove(0))
+    }
 
-                    break EkkoResponse::new(net, time, packet)?;
-                }
-            }
+    /// Send echo requests for all hops in range with default settings.
+    pub fn send_range(&self, hops: Range<u32>) -> Result<Vec<EkkoResponse>, EkkoError> {
+        self.send_range_with_settings(hops, Default::default())
+    }
 
-            if timepoint.elapsed() < timeout {
-                continue
-            }
+    /// Send echo requests for all hops in range with user defined settings.
+    pub fn send_range_with_settings(&self, hops: Range<u32>, EkkoSettings {
+        timeout, identifier, sequence, flow, length, pattern, tos
+    }: EkkoSettings) -> Result<Vec<EkkoResponse>, EkkoError> {
 
-            break EkkoResponse::Lacking({
+        let payload = payload(self.target_socket_address.ip(), flow, {
+            (length, &(pattern))
+        })?;
 
-                EkkoData { 
+        self.inner_reserve(hops.len(), payload.len())?;
 
-                    timepoint: timepoint, 
-                    elapsed: timepoint
-                        .elapsed(),
+        let timepoint = Instant::now();
+        let mut requests = self.inner_send_range(hops, tos, {
+            (identifier, sequence)
+        }, flow, &(payload))?;
 
-                    address: None,
-                    
-                    identifier: identifier,
-                    sequence: sequence,
-                    hops: hops,
-                    
-                }
-            })
-        };
+        self.inner_wait(&mut requests, timepoint + timeout)?;
 
-        Ok(result)
+        Ok(requests.finish())
     }
 
-    /// Send echo requests for all hops in range with default settings.
-    pub fn send_range(&self, hops: Range<u32>) -> Result<Vec<EkkoResponse>, EkkoError> {
-        self.send_range_with_settings(hops, Default::default())
+    /// Send echo requests for all hops in range with default settings,
+    /// responses are yielded as they arrive.
+    ///
+    /// ## Example
+    /// ```rust,no_run
+    /// use ekko::{
+    ///
+    ///     EkkoError,
+    ///     Ekko,
+    /// };
+    ///
+    /// fn main() -> Result<(), EkkoError> {
+    ///     let sender = Ekko::with_target([8, 8, 8, 8])?;
+    ///
+    ///     for ekko in sender.stream_range(1..32)? {
+    ///         println!("{:?}", ekko?)
+    ///     }
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn stream_range(&self, hops: Range<u32>) -> Result<EkkoStream, EkkoError> {
+        self.stream_range_with_settings(hops, Default::default())
     }
 
-    /// Send echo requests for all hops in range with user defined settings.
-    pub fn send_range_with_settings(&self, hops: Range<u32>, EkkoSettings { 
-        timeout, identifier, mut sequence 
-    }: EkkoSettings) -> Result<Vec<EkkoResponse>, EkkoError> {
+    /// Send echo requests for all hops in range with user defined settings,
+    /// responses are yielded as they arrive.
+    pub fn stream_range_with_settings(&self, hops: Range<u32>, EkkoSettings {
+        timeout, identifier, sequence, flow, length, pattern, tos
+    }: EkkoSettings) -> Result<EkkoStream, EkkoError> {
 
-        let mut buf: [u8; 256] = {
-            [0; 256]
-        };
-        
-        let mut echo_responses = Vec::with_capacity(hops.len());
-        let mut echo_requests = Vec::with_capacity(hops.len());
-        let mut echo_route = Vec::with_capacity(hops.len());
+        let payload = payload(self.target_socket_address.ip(), flow, {
+            (length, &(pattern))
+        })?;
+
+        self.inner_reserve(hops.len(), payload.len())?;
+
+        let requests = self.inner_send_range(hops, tos, {
+            (identifier, sequence)
+        }, flow, &(payload))?;
+
+        Ok(EkkoStream::new(requests, timeout))
+    }
 
+    pub(crate) fn inner_send_range(&self, hops: Range<u32>, tos: u8, pkt: (u16, u16), flow: Option<u16>, payload: &[u8]) -> Result<EkkoRequests, EkkoError> {
+        let (identifier, mut sequence) = pkt;
+
+        let mut requests = self.inner_requests(hops.len());
         let timepoint = Instant::now();
-        
+
         for hop in hops {
-            
-            self.inner_send(hop, {
+
+            let pkt = self.inner_send(hop, tos, {
                 (identifier, sequence)
-            })?;
-            
-            echo_requests.push((timepoint.clone(), identifier.clone(), sequence.clone(), hop.clone()));
+            }, flow, payload)?;
+
+            requests.push(timepoint, self.target_socket_address.ip(), pkt, hop);
             sequence = sequence.wrapping_add(1);
         }
-        
-        loop {
-
-            while let Some((address, packet)) = self.inner_recv(&mut buf)? {
-                if packet.is_echo_request()? {
-                    continue
-                }
-
-                for (request_timepoint, request_identifier, request_sequence, _) 
-                    in echo_requests.iter() {
-
-                    match (request_identifier.clone(), request_sequence.clone()) {
-                        x if x == (packet.get_identifier()?, packet.get_sequence()?) => echo_responses.push({
-                            (address.clone(), request_timepoint.clone(), request_timepoint.elapsed(), buf.to_vec())
-                        }),
-                        
-                        _ => continue
-                    }
-
-                    break
-                }
-            }
-
-            if (echo_requests.len() - echo_responses.len()) > 0 {
-                if timepoint.elapsed() < timeout {
-                    std::thread::yield_now();
-                    continue
-                }
-            }
-            
-            for (request_timepoint, request_identifier, request_sequence, request_hops) 
-                in echo_requests.iter() {
-
-                let previous_route_length = echo_route.len();
-                for (response_address, response_timepoint, response_elapsed, buf) 
-                    in echo_responses.iter() {
-
-                    let packet = match response_address {
-                        IpAddr::V4(_) => {
-
-                            let mut cursor = Cursor::new(buf.as_slice());
-                            let header_octets = ((cursor.read_u8().map_err(|e| {
-                                EkkoError::ResponseReadField("internet protocol header size", e.to_string())
-                            })? & 0x0F) * 4) as usize;
-
-                            EkkoPacket::V4(&(buf[header_octets..]))
-                        },
-
-                        IpAddr::V6(_) => {
-
-                            EkkoPacket::V6(&(buf[..]))
-                        },
-                    };
-
-                    match (request_identifier.clone(), request_sequence.clone()) {
-                        x if x == (packet.get_identifier()?, packet.get_sequence()?) => echo_route.push({
-                            let time = (response_timepoint.clone(), response_elapsed.clone());
-                            let net = (response_address.clone(), request_hops.clone());
-    
-                            EkkoResponse::new(net, time, packet)?
-                        }),
-
-                        _ => continue
-                    }
-
-                    break
-                }
-
-                if previous_route_length < echo_route.len() { 
-                    continue 
-                } 
-                
-                else {
-
-                    echo_route.push({
-
-                        EkkoResponse::Lacking({
-
-                            EkkoData { 
-
-                                timepoint: request_timepoint.clone(), 
-                                elapsed: request_timepoint
-                                    .elapsed(),
-
-                                address: None,
-
-                                identifier: request_identifier.clone(),
-                                sequence: request_sequence.clone(),
-                                hops: request_hops.clone(),
-
-                            }
-                        })
-                    });
-                }
-            }
-
-            break Ok(echo_route)
+
+        Ok(requests)
+    }
+
+    /// Requests having responses routed to them by the dispatcher.
+    pub(crate) fn inner_requests(&self, capacity: usize) -> EkkoRequests {
+        EkkoRequests::subscribed(capacity, EkkoSubscription {
+            dispatchers: vec![self.dispatcher.clone()],
+            inbox: Default::default(),
+        })
+    }
+
+    pub(crate) fn inner_wait(&self, requests: &mut EkkoRequests, deadline: Instant) -> Result<(), EkkoError> {
+        while !(requests.is_complete()) && Instant::now() < deadline {
+            requests.wait(deadline)?;
         }
+
+        Ok(())
     }
 
-    fn inner_send(&self, hops: u32, pkt: (u16, u16)) -> Result<(), EkkoError> {
-        let mut buf: [u8; 128] = [0; 128];
-        let request = EkkoPacket::new(&mut buf[..], pkt, {
-            (self.source_socket_address, self.target_socket_address)
+    /// Sender for the same target on a socket of its own, for changing
+    /// socket options without affecting other senders.
+    pub(crate) fn inner_detached(&self) -> Result<Ekko, EkkoError> {
+        let socket = self.socket();
+        let socket = EkkoSocket::with_device(socket.source(), socket.device(), Some(socket.mode()), {
+            socket.probe()
         })?;
 
-        match (self.source_socket_address, self.target_socket_address) {
+        let sender = Ekko::with_dispatcher(self.target_socket_address, EkkoDispatcher::new(socket)?)?;
+        sender.timestamps.store(self.timestamps.load(Ordering::Relaxed), Ordering::Relaxed);
 
-            (SocketAddr::V4(_), SocketAddr::V4(_)) => {
-                self.socket.set_ttl(hops).map_err(|e| {
-                    EkkoError::SocketSetMaxHopsIpv4({
-                        e.to_string()
-                    })
-                })?;
+        Ok(sender)
+    }
 
-                self.socket.send_to(request.as_slice(), {
-                    &(self.target_socket_address.into())
-                }).map_err(|e|  EkkoError::SocketSendIcmpv4({
-                    e.to_string()
-                }))?;
-            },
+    /// Sender for the same target on a socket of its own, sending requests
+    /// along with an IPv4 header built here. None unless the sender has a raw
+    /// IPv4 socket for echo requests, the kernel queues errors for others
+    /// with the header in place of the request.
+    pub(crate) fn inner_identified(&self) -> Option<Ekko> {
+        if !(self.target_socket_address.is_ipv4()) || self.mode() != EkkoMode::Raw || self.probe() != EkkoProbe::Icmp {
+            return None
+        }
 
-            (SocketAddr::V6(_), SocketAddr::V6(_)) => {
-                self.socket.set_unicast_hops_v6(hops).map_err(|e| {
-                    EkkoError::SocketSetMaxHopsIpv6({
-                        e.to_string()
-                    })
-                })?;
-
-                self.socket.send_to(request.as_slice(), {
-                    &(self.target_socket_address.into())
-                }).map_err(|e| EkkoError::SocketSendIcmpv6({
-                    e.to_string()
-                }))?;
-            },
+        let sender = self.inner_detached().ok()?;
+        sender.socket().set_header_included().ok()?;
 
-            (src, dst) => {
-                return Err(EkkoError::SocketIpMismatch { 
-                    src: src.to_string(), 
-                    dst: dst.to_string() 
-                })
-            },
-        };
+        Some(sender)
+    }
 
-        Ok(())
+    /// Source address requests are built over.
+    pub(crate) fn inner_source(&self) -> IpAddr {
+        self.source_socket_address.ip()
+    }
+
+    pub(crate) fn inner_reserve(&self, responses: usize, octets: usize) -> Result<(), EkkoError> {
+        self.socket().reserve(responses, octets)
     }
 
-    fn inner_recv<'a>(&self, buf: &'a mut [u8]) -> Result<Option<(IpAddr, EkkoPacket<'a>)>, EkkoError> {
-        let result = self.socket.recv_from(unsafe { 
-            std::mem::transmute(&mut buf[..]) 
-        });
+    pub(crate) fn inner_dont_fragment(&self, enable: bool) -> Result<(), EkkoError> {
+        self.socket().set_dont_fragment(enable)
+    }
 
-        if let Ok((_, responder)) = result {
-            let responding_address = match self.source_socket_address {
+    /// Send a single request, returns the identifier and sequence as they
+    /// were put on the wire.
+    pub(crate) fn inner_send(&self, hops: u32, tos: u8, pkt: (u16, u16), flow: Option<u16>, payload: &[u8]) -> Result<EkkoSent, EkkoError> {
+        let pkt = match (self.identifier, pkt) {
+            (Some(identifier), (_, sequence)) => (identifier, sequence),
+            (None, pkt) => pkt,
+        };
 
-                SocketAddr::V4(_) => IpAddr::V4(responder.as_socket_ipv4()
-                    .ok_or(EkkoError::SocketReceiveNoIpv4)?.ip()
-                    .clone()),
-                    
-                SocketAddr::V6(_) => IpAddr::V6(responder.as_socket_ipv6()
-                    .ok_or(EkkoError::SocketReceiveNoIpv6)?.ip()
-                    .clone()),
-            };
+        // Room for the largest header, that of a segment, and the payload.
+        let mut buf: Vec<u8> = vec![0; 20 + payload.len()];
 
-            match responding_address {
+        let (request, pkt) = match self.socket().probe() {
 
-                IpAddr::V4(_) => Ok(Some((responding_address, {
-                    let mut cursor = Cursor::new(&mut buf[..]);
-                    let header_octets = ((cursor.read_u8().map_err(|e| {
-                        EkkoError::ResponseReadField("internet protocol header size", e.to_string())
-                    })? & 0x0F) * 4) as usize;
+            EkkoProbe::Icmp => (EkkoPacket::new(&mut buf[..], pkt, flow, payload, {
+                (self.source_socket_address, self.target_socket_address)
+            })?.as_slice(), pkt),
 
-                    EkkoPacket::V4(&(buf[header_octets..]))
-                }))),
+            EkkoProbe::Udp(_) => (payload, pkt),
 
-                IpAddr::V6(_) => Ok(Some((responding_address, {
-                    EkkoPacket::V6(&(buf[..]))
-                }))),
-            }
-        }
-        
-        else {
-            
-            Ok(None)
+            EkkoProbe::Tcp(port) => {
+                let (identifier, sequence) = pkt;
+                let pkt = (flow.unwrap_or(identifier), sequence);
+
+                (EkkoPacket::new_tcp(&mut buf[..], pkt, port, {
+                    (self.source_socket_address.ip(), self.target_socket_address.ip())
+                })?.as_slice(), pkt)
+            },
+        };
+
+        let sent = self.socket().send(self.target_socket_address, hops, tos, {
+            (request, pkt)
+        })?;
+
+        // Without a key the send timestamp is never matched.
+        match self.timestamps.load(Ordering::Relaxed) {
+            true => Ok(sent),
+            false => Ok(EkkoSent { key: None, ..sent }),
         }
     }
 }
+
+#[cfg(unix)]
+impl std::os::unix::io::AsRawFd for Ekko {
+    fn as_raw_fd(&self) -> std::os::unix::io::RawFd {
+        self.socket().as_raw_fd()
+    }
+}
@@ -0,0 +1,259 @@
+use std::{
+
+    time::{
+
+        Duration,
+        Instant,
+    },
+};
+
+use super::{
+
+    requests::{EkkoRequests},
+    packets::{payload},
+    error::{EkkoError},
+
+    sender::{
+
+        EkkoSettings,
+        Ekko,
+    },
+
+    responses::{EkkoResponse},
+};
+
+/// Continuous echo requests to the senders target, the likes of
+/// `ping -i 0.2 -c 100 -w 30`. Every request carries the same identifier and
+/// the next sequence, wrapping around. Requests stay outstanding until their
+/// timeout, so replies arriving after later requests were sent still match.
+/// Responses come out in the order requests were sent.
+///
+/// ## Example
+/// ```rust,no_run
+/// use std::time::{Duration};
+///
+/// use ekko::{
+///
+///     EkkoSession,
+///     EkkoError,
+///     Ekko,
+/// };
+///
+/// fn main() -> Result<(), EkkoError> {
+///     let sender = Ekko::with_target([8, 8, 8, 8])?;
+///     let session = EkkoSession::new(&sender)
+///         .interval(Duration::from_millis(200))
+///         .deadline(Duration::from_secs(30))
+///         .count(100);
+///
+///     for ekko in session {
+///         println!("{:?}", ekko?)
+///     }
+///
+///     Ok(())
+/// }
+/// ```
+pub struct EkkoSession<'a> {
+
+    sender: &'a Ekko,
+    settings: EkkoSettings,
+
+    hops: u32,
+
+    interval: Duration,
+    adaptive: bool,
+
+    count: Option<usize>,
+    deadline: Option<Duration>,
+
+    requests: EkkoRequests,
+
+    started: Option<Instant>,
+    next: Instant,
+    sent: usize,
+    done: bool,
+}
+
+impl<'a> EkkoSession<'a> {
+
+    /// Build a session with default settings, sends a request every second
+    /// until dropped.
+    pub fn new(sender: &'a Ekko) -> EkkoSession<'a> {
+        EkkoSession::with_settings(sender, Default::default())
+    }
+
+    /// Build a session with user defined settings, the sequence in settings
+    /// is the one of the first request and the timeout applies to each.
+    pub fn with_settings(sender: &'a Ekko, settings: EkkoSettings) -> EkkoSession<'a> {
+        EkkoSession {
+
+            sender,
+            settings,
+
+            hops: 64,
+
+            interval: {
+
+                Duration::from_millis(1000)
+            },
+
+            adaptive: false,
+
+            count: None,
+            deadline: None,
+
+            requests: sender.inner_requests(0),
+
+            started: None,
+            next: Instant::now(),
+            sent: 0,
+            done: false,
+        }
+    }
+
+    /// Number of hops of each request.
+    pub fn hops(mut self, hops: u32) -> EkkoSession<'a> {
+        self.hops = hops;
+        self
+    }
+
+    /// Time to wait between consecutive requests.
+    pub fn interval(mut self, interval: Duration) -> EkkoSession<'a> {
+        self.interval = interval;
+        self
+    }
+
+    /// Send the next request as soon as every earlier one is answered,
+    /// waiting at most the interval otherwise.
+    pub fn adaptive(mut self, adaptive: bool) -> EkkoSession<'a> {
+        self.adaptive = adaptive;
+        self
+    }
+
+    /// Number of requests to send before stopping.
+    pub fn count(mut self, count: usize) -> EkkoSession<'a> {
+        self.count = Some(count);
+        self
+    }
+
+    /// Time after which the session stops regardless of how many requests
+    /// were sent, outstanding ones are marked as lacking.
+    pub fn deadline(mut self, deadline: Duration) -> EkkoSession<'a> {
+        self.deadline = Some(deadline);
+        self
+    }
+
+    /// Number of requests sent so far.
+    pub fn sent(&self) -> usize {
+        self.sent
+    }
+
+    fn start(&mut self) -> Result<Instant, EkkoError> {
+        if let Some(started) = self.started {
+            return Ok(started)
+        }
+
+        // Enough room for every request that may be outstanding at once.
+        let outstanding = match self.interval.as_nanos() {
+            0 => 64,
+            interval => (self.settings.timeout.as_nanos() / interval) as usize + 1,
+        };
+
+        self.sender.inner_reserve(outstanding.min(1024), self.settings.length)?;
+
+        let started = Instant::now();
+
+        self.started = Some(started);
+        self.next = started;
+
+        Ok(started)
+    }
+
+    fn send(&mut self, now: Instant) -> Result<(), EkkoError> {
+        let EkkoSettings { identifier, sequence, flow, length, ref pattern, tos, .. } = self.settings;
+
+        let payload = payload(self.sender.target(), flow, {
+            (length, pattern)
+        })?;
+
+        let pkt = self.sender.inner_send(self.hops, tos, {
+            (identifier, sequence)
+        }, flow, &(payload))?;
+
+        self.requests.push(now, self.sender.target(), pkt, self.hops);
+        self.settings.sequence = sequence.wrapping_add(1);
+
+        self.sent += 1;
+        self.next = now + self.interval;
+
+        Ok(())
+    }
+
+    fn inner_next(&mut self) -> Result<Option<EkkoResponse>, EkkoError> {
+        let started = self.start()?;
+
+        let deadline = self.deadline.map(|deadline| started + deadline);
+
+        loop {
+
+            let now = Instant::now();
+            let timeout = match deadline {
+
+                Some(deadline) if now >= deadline => {
+                    self.done = true;
+                    Duration::from_secs(0)
+                },
+
+                _ => self.settings.timeout,
+            };
+
+            if let Some(response) = self.requests.pop(timeout) {
+                return Ok(Some(response))
+            }
+
+            if self.count.map(|count| self.sent >= count).unwrap_or(false) {
+                self.done = true;
+            }
+
+            if self.done {
+                match self.requests.deadline(timeout) {
+                    Some(expires) => {
+                        self.requests.wait(expires)?;
+                        continue
+                    },
+                    None => return Ok(None),
+                }
+            }
+
+            if now >= self.next || (self.adaptive && self.requests.is_complete()) {
+                self.send(now)?;
+                continue
+            }
+
+            // Wake for whatever comes first, a response, the next request
+            // being due, the oldest one expiring or the session ending.
+            let wake = [Some(self.next), self.requests.deadline(timeout), deadline]
+                .iter().flatten().min().copied().unwrap_or(self.next);
+
+            self.requests.wait(wake)?;
+        }
+    }
+}
+
+impl<'a> Iterator for EkkoSession<'a> {
+    type Item = Result<EkkoResponse, EkkoError>;
+
+    /// Wait for the response to the oldest outstanding request, sending
+    /// further requests as they become due. Stops sending after an error.
+    fn next(&mut self) -> Option<Result<EkkoResponse, EkkoError>> {
+        match self.inner_next() {
+
+            Err(e) => {
+                self.done = true;
+                Some(Err(e))
+            },
+
+            result => result.transpose(),
+        }
+    }
+}
@@ -0,0 +1,1337 @@
+use std::{
+
+    mem::{MaybeUninit},
+    time::{Duration},
+    io::{
+
+        ErrorKind,
+        Error,
+    },
+
+    sync::{Mutex},
+    sync::atomic::{
+
+        AtomicBool,
+        AtomicU32,
+        AtomicU16,
+        Ordering,
+    },
+
+    net::{
+
+        SocketAddrV6,
+        SocketAddrV4,
+        SocketAddr,
+        Ipv4Addr,
+    },
+};
+
+use socket2::{
+
+    Protocol,
+    Domain,
+    Socket,
+    Type,
+};
+
+use super::{
+
+    packets::{EkkoPacket},
+    error::{EkkoError},
+};
+
+/// Size of receive buffers, enough for the largest response along with the
+/// error message rebuilt around quoted requests.
+pub(crate) const RESPONSE_OCTETS: usize = 65535 + 8 + 40;
+
+/// Number of responses sockets have room for from the start.
+const RESERVED_RESPONSES: usize = 64;
+
+/// Whether a send was refused locally for exceeding the MTU.
+#[cfg(unix)]
+fn is_too_big(error: &Error) -> bool {
+    error.raw_os_error() == Some(libc::EMSGSIZE)
+}
+
+#[cfg(not(unix))]
+fn is_too_big(_: &Error) -> bool {
+    false
+}
+
+/// Have socket send from and receive on a device only, an interface or a VRF.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn bind_device(socket: &Socket, device: &str) -> Result<(), EkkoError> {
+    socket.bind_device(Some(device.as_bytes())).map_err(|e| {
+        EkkoError::SocketBindDevice(device.to_owned(), e.to_string())
+    })
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+fn bind_device(_: &Socket, device: &str) -> Result<(), EkkoError> {
+    Err(EkkoError::SocketBindDevice(device.to_owned(), {
+        "unsupported platform".to_owned()
+    }))
+}
+
+/// Set the traffic class of IPv6 packets sent from socket.
+#[cfg(any(
+    target_os = "linux",
+    target_os = "android",
+    target_os = "freebsd",
+    target_os = "netbsd",
+    target_os = "openbsd",
+    target_os = "macos",
+))]
+fn set_traffic_class(socket: &Socket, tos: u8) -> Result<(), Error> {
+    socket.set_tclass_v6(tos as u32)
+}
+
+#[cfg(not(any(
+    target_os = "linux",
+    target_os = "android",
+    target_os = "freebsd",
+    target_os = "netbsd",
+    target_os = "openbsd",
+    target_os = "macos",
+)))]
+fn set_traffic_class(_: &Socket, tos: u8) -> Result<(), Error> {
+    match tos {
+        0 => Ok(()),
+        _ => Err(Error::new(ErrorKind::Unsupported, "unsupported platform")),
+    }
+}
+
+/// Index of interface by name, the scope of link-local addresses on it.
+#[cfg(unix)]
+pub(crate) fn interface_index(interface: &str) -> Result<u32, Error> {
+    let name = std::ffi::CString::new(interface).map_err(|e| {
+        Error::new(ErrorKind::InvalidInput, e)
+    })?;
+
+    match unsafe { libc::if_nametoindex(name.as_ptr()) } {
+        0 => Err(Error::last_os_error()),
+        index => Ok(index),
+    }
+}
+
+#[cfg(not(unix))]
+pub(crate) fn interface_index(_: &str) -> Result<u32, Error> {
+    Err(Error::new(ErrorKind::Unsupported, "unsupported platform"))
+}
+
+/// Kind of socket used for sending echo requests.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EkkoMode {
+    /// Raw socket, requires elevated privileges (or `CAP_NET_RAW`).
+    Raw,
+    /// Unprivileged datagram socket (Linux ping socket), the kernel sets the
+    /// identifier and strips the internet protocol header of responses.
+    Datagram,
+}
+
+/// Kind of request sent to targets.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EkkoProbe {
+    /// Echo request, the default.
+    Icmp,
+    /// Datagram to a high port (Van Jacobson traceroute), the destination
+    /// port is the given base port plus the sequence, the identifier is the
+    /// source port. Errors are read from the sockets error queue, so no
+    /// privileges are needed. Port unreachable from the target counts as
+    /// reaching the destination. Traditionally the base port is 33434.
+    Udp(u16),
+    /// Connection request (SYN) to the given port, the identifier is the
+    /// source port, or the flow if one is given. Any reply of the target,
+    /// accepting or refusing, counts as reaching the destination. Always uses
+    /// a raw socket.
+    Tcp(u16),
+}
+
+impl EkkoProbe {
+
+    fn protocol(&self, source: SocketAddr) -> u8 {
+        match (self, source) {
+            (EkkoProbe::Icmp, SocketAddr::V4(_)) => 1,
+            (EkkoProbe::Icmp, SocketAddr::V6(_)) => 58,
+            (EkkoProbe::Udp(_), _) => 17,
+            (EkkoProbe::Tcp(_), _) => 6,
+        }
+    }
+}
+
+/// Request as put on the wire.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct EkkoSent {
+    pub(crate) identifier: u16,
+    pub(crate) sequence: u16,
+    /// Key the kernel files the send timestamp under, when enabled.
+    pub(crate) key: Option<u32>,
+    /// Identification of the IPv4 header, when built here rather than by
+    /// the kernel.
+    pub(crate) identification: Option<u16>,
+
+    /// Octets following the internet protocol header.
+    pub(crate) length: u16,
+    /// Checksum as handed to the kernel, unknown when the kernel fills it in.
+    /// The kernel fills in that of ICMPv6 as well, which only matches when
+    /// the request was built over the actual source address.
+    pub(crate) checksum: Option<u16>,
+}
+
+/// What the socket had to tell.
+pub(crate) enum EkkoReceived<'a> {
+    /// Response from address, along with the kernels receive timestamp and
+    /// the time to live it arrived with. The address carries the scope of
+    /// link-local responders. Set when rebuilt from the error queue, the
+    /// quoted header then lacks what the kernel keeps to itself, like the
+    /// type of service.
+    Response(SocketAddr, EkkoPacket<'a>, Option<Duration>, Option<u8>, bool),
+    /// Kernels send timestamp of the request sent under key.
+    Transmitted(u32, Duration),
+}
+
+/// Error message rebuilt in a buffer, or a send timestamp, kept apart from
+/// the buffer so it is free to read into again when there was none.
+enum EkkoQueued {
+    Response(SocketAddr, usize, Option<Duration>, Option<u8>),
+    Transmitted(u32, Duration),
+}
+
+impl EkkoQueued {
+    fn received(self, buf: &[u8]) -> EkkoReceived<'_> {
+        match self {
+
+            EkkoQueued::Response(responder @ SocketAddr::V4(_), length, timestamp, ttl) => {
+                EkkoReceived::Response(responder, EkkoPacket::V4(&(buf[..length])), timestamp, ttl, true)
+            },
+
+            EkkoQueued::Response(responder @ SocketAddr::V6(_), length, timestamp, ttl) => {
+                EkkoReceived::Response(responder, EkkoPacket::V6(&(buf[..length])), timestamp, ttl, true)
+            },
+
+            EkkoQueued::Transmitted(key, timestamp) => {
+                EkkoReceived::Transmitted(key, timestamp)
+            },
+        }
+    }
+}
+
+/// Message read from a socket, along with the ancillary data of interest.
+struct EkkoMessage {
+    length: usize,
+    /// Sender, or for the error queue the destination of the request.
+    address: Option<SocketAddr>,
+    /// Software receive timestamp, or send timestamp for the error queue.
+    timestamp: Option<Duration>,
+    /// Extended error as origin, type, code, info and data.
+    error: Option<(u8, u8, u8, u32, u32)>,
+    /// Address of whoever sent the error message.
+    offender: Option<SocketAddr>,
+    /// Time to live (IPv4) or hop limit (IPv6) the message arrived with.
+    ttl: Option<u8>,
+}
+
+pub(crate) struct EkkoSocket {
+
+    source_socket_address: SocketAddr,
+    probe: EkkoProbe,
+    mode: EkkoMode,
+
+    /// Interface or VRF the socket is bound to.
+    device: Option<String>,
+
+    timestamps: AtomicBool,
+    transmitted: AtomicU32,
+
+    /// Whether requests are sent along with their IPv4 header, and the
+    /// identification of the next one.
+    included: AtomicBool,
+    identification: AtomicU16,
+
+    /// Held while setting per request options and sending, clones sharing
+    /// the socket would otherwise send with each others options. Holds the
+    /// type of service or traffic class last set.
+    sending: Mutex<u8>,
+
+    socket: Socket,
+}
+
+impl EkkoSocket {
+
+    /// Open a socket bound to source address and device, an interface or a
+    /// VRF. Falls back to datagram mode when no mode is given and raw sockets
+    /// are denied. Probes other than echo requests decide the mode
+    /// themselves.
+    pub(crate) fn with_device(source: SocketAddr, device: Option<&str>, mode: Option<EkkoMode>, probe: EkkoProbe) -> Result<EkkoSocket, EkkoError> {
+        let result = match (probe, mode) {
+
+            (EkkoProbe::Udp(_), _) => EkkoSocket::open(source, EkkoMode::Datagram, probe).map(|socket| {
+                (socket, EkkoMode::Datagram)
+            }),
+
+            (EkkoProbe::Tcp(_), _) => EkkoSocket::open(source, EkkoMode::Raw, probe).map(|socket| {
+                (socket, EkkoMode::Raw)
+            }),
+
+            (_, Some(mode)) => EkkoSocket::open(source, mode, probe).map(|socket| (socket, mode)),
+            (_, None) => match EkkoSocket::open(source, EkkoMode::Raw, probe) {
+
+                Err(e) if e.kind() == ErrorKind::PermissionDenied => {
+                    EkkoSocket::open(source, EkkoMode::Datagram, probe).map(|socket| {
+                        (socket, EkkoMode::Datagram)
+                    })
+                },
+
+                result => result.map(|socket| (socket, EkkoMode::Raw)),
+            },
+        };
+
+        let (socket, mode) = result.map_err(|e| match (probe, source) {
+            (EkkoProbe::Icmp, SocketAddr::V4(_)) => EkkoError::SocketCreateIcmpv4(e.to_string()),
+            (EkkoProbe::Icmp, SocketAddr::V6(_)) => EkkoError::SocketCreateIcmpv6(e.to_string()),
+            (EkkoProbe::Udp(_), _) => EkkoError::SocketCreateUdp(e.to_string()),
+            (EkkoProbe::Tcp(_), _) => EkkoError::SocketCreateTcp(e.to_string()),
+        })?;
+
+        socket.set_nonblocking(true).map_err(|e| {
+            EkkoError::SocketSetNonBlocking(true, e.to_string())
+        })?;
+
+        if let Some(device) = device {
+            bind_device(&(socket), device)?;
+        }
+
+        match source {
+
+            SocketAddr::V4(_) => {
+                socket.bind(&(source.into())).map_err(|e| {
+                    EkkoError::SocketBindIpv4(source.to_string(), e.to_string())
+                })?;
+            },
+
+            SocketAddr::V6(_) => {
+                socket.bind(&(source.into())).map_err(|e| {
+                    EkkoError::SocketBindIpv6(source.to_string(), e.to_string())
+                })?;
+            },
+        }
+
+        let socket = EkkoSocket {
+
+            source_socket_address: source,
+            probe,
+            mode,
+
+            device: device.map(str::to_owned),
+
+            timestamps: AtomicBool::new(false),
+            transmitted: AtomicU32::new(0),
+
+            included: AtomicBool::new(false),
+            identification: AtomicU16::new(rand::random()),
+
+            sending: Mutex::new(0),
+
+            socket,
+        };
+
+        if socket.is_error_queued() {
+            EkkoSocket::set_recv_err(&(socket.socket), source)?;
+        }
+
+        EkkoSocket::set_recv_ttl(&(socket.socket), source)?;
+
+        // Room for a burst of responses before anyone reserves more, shared
+        // sockets take responses to many senders at once.
+        socket.reserve(RESERVED_RESPONSES, 0)?;
+
+        // Every sender of the socket decides for itself whether to use the
+        // timestamps, they are left off where the kernel has none to give.
+        socket.enable_timestamps().ok();
+
+        Ok(socket)
+    }
+
+    fn open(source: SocketAddr, mode: EkkoMode, probe: EkkoProbe) -> Result<Socket, Error> {
+        let kind = match mode {
+            EkkoMode::Datagram => Type::DGRAM,
+            EkkoMode::Raw => Type::RAW,
+        };
+
+        let protocol = match (probe, source) {
+            (EkkoProbe::Icmp, SocketAddr::V4(_)) => Protocol::ICMPV4,
+            (EkkoProbe::Icmp, SocketAddr::V6(_)) => Protocol::ICMPV6,
+            (EkkoProbe::Udp(_), _) => Protocol::UDP,
+            (EkkoProbe::Tcp(_), _) => Protocol::TCP,
+        };
+
+        match source {
+            SocketAddr::V4(_) => Socket::new(Domain::IPV4, kind, Some(protocol)),
+            SocketAddr::V6(_) => Socket::new(Domain::IPV6, kind, Some(protocol)),
+        }
+    }
+
+    /// Whether errors are read from the sockets error queue instead of
+    /// arriving as messages of their own.
+    fn is_error_queued(&self) -> bool {
+        self.mode == EkkoMode::Datagram || self.probe != EkkoProbe::Icmp
+    }
+
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    fn set_recv_err(socket: &Socket, source: SocketAddr) -> Result<(), EkkoError> {
+        use std::os::unix::io::{AsRawFd};
+
+        let (level, name) = match source {
+            SocketAddr::V4(_) => (libc::SOL_IP, libc::IP_RECVERR),
+            SocketAddr::V6(_) => (libc::SOL_IPV6, libc::IPV6_RECVERR),
+        };
+
+        let enable: libc::c_int = 1;
+        let result = unsafe {
+            libc::setsockopt(socket.as_raw_fd(), level, name, {
+                &(enable) as *const libc::c_int as *const libc::c_void
+            }, std::mem::size_of::<libc::c_int>() as libc::socklen_t)
+        };
+
+        if result < 0 {
+            return Err(EkkoError::SocketSetReceiveErrors({
+                Error::last_os_error().to_string()
+            }))
+        }
+
+        Ok(())
+    }
+
+    #[cfg(not(any(target_os = "linux", target_os = "android")))]
+    fn set_recv_err(_: &Socket, _: SocketAddr) -> Result<(), EkkoError> {
+        Ok(())
+    }
+
+    /// Have the time to live (IPv4) or hop limit (IPv6) of messages passed
+    /// as ancillary data, raw IPv4 sockets see it in the header as well.
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    fn set_recv_ttl(socket: &Socket, source: SocketAddr) -> Result<(), EkkoError> {
+        use std::os::unix::io::{AsRawFd};
+
+        let (level, name) = match source {
+            SocketAddr::V4(_) => (libc::SOL_IP, libc::IP_RECVTTL),
+            SocketAddr::V6(_) => (libc::SOL_IPV6, libc::IPV6_RECVHOPLIMIT),
+        };
+
+        let enable: libc::c_int = 1;
+        let result = unsafe {
+            libc::setsockopt(socket.as_raw_fd(), level, name, {
+                &(enable) as *const libc::c_int as *const libc::c_void
+            }, std::mem::size_of::<libc::c_int>() as libc::socklen_t)
+        };
+
+        if result < 0 {
+            return Err(EkkoError::SocketSetReceiveTtl({
+                Error::last_os_error().to_string()
+            }))
+        }
+
+        Ok(())
+    }
+
+    #[cfg(not(any(target_os = "linux", target_os = "android")))]
+    fn set_recv_ttl(_: &Socket, _: SocketAddr) -> Result<(), EkkoError> {
+        Ok(())
+    }
+
+    /// Set or clear the don't fragment bit of requests. While set, requests
+    /// are neither fragmented nor held to the cached path MTU, so each one
+    /// makes it onto the wire for routers to refuse.
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    pub(crate) fn set_dont_fragment(&self, enable: bool) -> Result<(), EkkoError> {
+        use std::os::unix::io::{AsRawFd};
+
+        let options: &[(libc::c_int, libc::c_int, libc::c_int)] = match (self.source_socket_address, enable) {
+
+            (SocketAddr::V4(_), true) => &[(libc::SOL_IP, libc::IP_MTU_DISCOVER, libc::IP_PMTUDISC_PROBE)],
+            (SocketAddr::V4(_), false) => &[(libc::SOL_IP, libc::IP_MTU_DISCOVER, libc::IP_PMTUDISC_WANT)],
+
+            (SocketAddr::V6(_), true) => &[
+                (libc::SOL_IPV6, libc::IPV6_MTU_DISCOVER, libc::IPV6_PMTUDISC_PROBE),
+                (libc::SOL_IPV6, libc::IPV6_DONTFRAG, 1),
+            ],
+
+            (SocketAddr::V6(_), false) => &[
+                (libc::SOL_IPV6, libc::IPV6_MTU_DISCOVER, libc::IPV6_PMTUDISC_WANT),
+                (libc::SOL_IPV6, libc::IPV6_DONTFRAG, 0),
+            ],
+        };
+
+        for (level, name, value) in options {
+            let result = unsafe {
+                libc::setsockopt(self.socket.as_raw_fd(), *level, *name, {
+                    value as *const libc::c_int as *const libc::c_void
+                }, std::mem::size_of::<libc::c_int>() as libc::socklen_t)
+            };
+
+            if result < 0 {
+                return Err(EkkoError::SocketSetDontFragment({
+                    Error::last_os_error().to_string()
+                }))
+            }
+        }
+
+        Ok(())
+    }
+
+    #[cfg(not(any(target_os = "linux", target_os = "android")))]
+    pub(crate) fn set_dont_fragment(&self, _: bool) -> Result<(), EkkoError> {
+        Err(EkkoError::SocketSetDontFragment({
+            "unsupported platform".to_owned()
+        }))
+    }
+
+    /// Make room for given number of responses, each carrying up to given
+    /// octets of payload.
+    pub(crate) fn reserve(&self, responses: usize, octets: usize) -> Result<(), EkkoError> {
+        let size = responses.saturating_mul(octets.saturating_add(1024).max(2048));
+
+        match self.socket.recv_buffer_size() {
+            Ok(current) if current >= size => Ok(()),
+            _ => self.socket.set_recv_buffer_size(size).map_err(|e| {
+                EkkoError::SocketSetReceiveBufferSize(e.to_string())
+            }),
+        }
+    }
+
+    pub(crate) fn mode(&self) -> EkkoMode {
+        self.mode
+    }
+
+    pub(crate) fn probe(&self) -> EkkoProbe {
+        self.probe
+    }
+
+    pub(crate) fn source(&self) -> SocketAddr {
+        self.source_socket_address
+    }
+
+    pub(crate) fn device(&self) -> Option<&str> {
+        self.device.as_deref()
+    }
+
+    /// Source address the kernel picks for sending to target, found by
+    /// connecting a throwaway datagram socket bound to the same device when
+    /// the source is unspecified.
+    pub(crate) fn route(&self, target: SocketAddr) -> Result<SocketAddr, EkkoError> {
+        if !(self.source_socket_address.ip().is_unspecified()) {
+            return Ok(self.source_socket_address)
+        }
+
+        let domain = match target {
+            SocketAddr::V4(_) => Domain::IPV4,
+            SocketAddr::V6(_) => Domain::IPV6,
+        };
+
+        let mut destination = target;
+        destination.set_port(9);
+
+        let socket = Socket::new(domain, Type::DGRAM, None).map_err(|e| {
+            EkkoError::SocketRoute(target.ip().to_string(), e.to_string())
+        })?;
+
+        if let Some(device) = self.device() {
+            bind_device(&(socket), device)?;
+        }
+
+        socket.connect(&(destination.into())).map_err(|e| {
+            EkkoError::SocketRoute(target.ip().to_string(), e.to_string())
+        })?;
+
+        socket.local_addr().ok()
+            .and_then(|address| address.as_socket())
+            .ok_or_else(|| EkkoError::SocketRoute(target.ip().to_string(), {
+                "no local address".to_owned()
+            }))
+    }
+
+    /// Send request with given hops and type of service (IPv4) or traffic
+    /// class (IPv6), returns the identifier and sequence as they were put on
+    /// the wire. The kernel decides the identifier for datagram sockets,
+    /// datagram probes carry them in their ports.
+    pub(crate) fn send(&self, mut target: SocketAddr, hops: u32, tos: u8, request: (&[u8], (u16, u16))) -> Result<EkkoSent, EkkoError> {
+        let (request, (identifier, sequence)) = request;
+
+        if let EkkoProbe::Udp(port) = self.probe {
+            target.set_port(port.wrapping_add(sequence));
+        }
+
+        let mut sending = self.sending.lock().unwrap_or_else(|e| e.into_inner());
+
+        let mut identification: Option<u16> = None;
+
+        match (self.source_socket_address, target) {
+
+            (SocketAddr::V4(source), SocketAddr::V4(destination)) if self.included.load(Ordering::Relaxed) => {
+                // Zero has the kernel pick the identification after all.
+                let included = self.identification.fetch_add(1, Ordering::Relaxed).max(1);
+                let packet = EkkoSocket::header(request, (*source.ip(), *destination.ip()), {
+                    (hops.min(255) as u8, tos, included, self.probe.protocol(self.source_socket_address))
+                });
+
+                self.send_to(&(packet), target).map_err(|e| match is_too_big(&(e)) {
+                    true => EkkoError::SocketSendTooBig(e.to_string()),
+                    false => EkkoError::SocketSendIcmpv4(e.to_string()),
+                })?;
+
+                identification = Some(included);
+            },
+
+            (SocketAddr::V4(_), SocketAddr::V4(_)) => {
+                self.socket.set_ttl(hops).map_err(|e| {
+                    EkkoError::SocketSetMaxHopsIpv4({
+                        e.to_string()
+                    })
+                })?;
+
+                if *sending != tos {
+                    self.socket.set_tos(tos as u32).map_err(|e| {
+                        EkkoError::SocketSetTosIpv4(e.to_string())
+                    })?;
+
+                    *sending = tos;
+                }
+
+                self.send_to(request, target).map_err(|e| match is_too_big(&(e)) {
+                    true => EkkoError::SocketSendTooBig(e.to_string()),
+                    false => EkkoError::SocketSendIcmpv4(e.to_string()),
+                })?;
+            },
+
+            (SocketAddr::V6(_), SocketAddr::V6(_)) => {
+                self.socket.set_unicast_hops_v6(hops).map_err(|e| {
+                    EkkoError::SocketSetMaxHopsIpv6({
+                        e.to_string()
+                    })
+                })?;
+
+                if *sending != tos {
+                    set_traffic_class(&(self.socket), tos).map_err(|e| {
+                        EkkoError::SocketSetTosIpv6(e.to_string())
+                    })?;
+
+                    *sending = tos;
+                }
+
+                self.send_to(request, target).map_err(|e| match is_too_big(&(e)) {
+                    true => EkkoError::SocketSendTooBig(e.to_string()),
+                    false => EkkoError::SocketSendIcmpv6(e.to_string()),
+                })?;
+            },
+
+            (src, dst) => {
+                return Err(EkkoError::SocketIpMismatch {
+                    src: src.to_string(),
+                    dst: dst.to_string()
+                })
+            },
+        };
+
+        // The kernel numbers sends from zero once timestamps are enabled.
+        let key = match self.timestamps.load(Ordering::Relaxed) {
+            true => Some(self.transmitted.fetch_add(1, Ordering::Relaxed)),
+            false => None,
+        };
+
+        drop(sending);
+
+        let sequence = match self.probe {
+            EkkoProbe::Udp(_) => target.port(),
+            EkkoProbe::Icmp | EkkoProbe::Tcp(_) => sequence,
+        };
+
+        // Datagram sockets leave the checksum to the kernel, which rewrites
+        // the identifier of echo requests.
+        let checksum = match (self.mode, self.probe) {
+            (EkkoMode::Raw, EkkoProbe::Icmp) => request.get(2..4),
+            (EkkoMode::Raw, EkkoProbe::Tcp(_)) => request.get(16..18),
+            _ => None,
+        }.map(|octets| u16::from_be_bytes([octets[0], octets[1]]));
+
+        let length = match self.probe {
+            EkkoProbe::Udp(_) => request.len() + 8,
+            EkkoProbe::Icmp | EkkoProbe::Tcp(_) => request.len(),
+        } as u16;
+
+        let identifier = match self.mode {
+
+            EkkoMode::Raw => identifier,
+            EkkoMode::Datagram => {
+
+                let address = self.socket.local_addr().map_err(|e| {
+                    EkkoError::SocketReadLocalAddress(e.to_string())
+                })?;
+
+                address.as_socket().map(|address| address.port())
+                    .unwrap_or(identifier)
+            },
+        };
+
+        Ok(EkkoSent {
+
+            identifier,
+            sequence,
+
+            key,
+            identification,
+
+            length,
+            checksum,
+        })
+    }
+
+    /// Request along with an IPv4 header, the kernel fills in the length,
+    /// the checksum and an unspecified source address.
+    fn header(request: &[u8], net: (Ipv4Addr, Ipv4Addr), (ttl, tos, identification, protocol): (u8, u8, u16, u8)) -> Vec<u8> {
+        let (source, destination) = net;
+
+        let mut packet: Vec<u8> = vec![0; 20 + request.len()];
+
+        packet[0] = 0x45;
+        packet[1] = tos;
+        packet[2..4].copy_from_slice(&(((20 + request.len()) as u16).to_be_bytes()));
+        packet[4..6].copy_from_slice(&(identification.to_be_bytes()));
+        // Don't fragment, as the kernel sets it for requests of its own.
+        packet[6] = 0x40;
+        packet[8] = ttl;
+        packet[9] = protocol;
+        packet[12..16].copy_from_slice(&(source.octets()));
+        packet[16..20].copy_from_slice(&(destination.octets()));
+        packet[20..].copy_from_slice(request);
+
+        packet
+    }
+
+    /// Send requests along with an IPv4 header built here, so the
+    /// identification of each is known. Only raw IPv4 sockets take headers.
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    pub(crate) fn set_header_included(&self) -> Result<(), EkkoError> {
+        use std::os::unix::io::{AsRawFd};
+
+        if let (EkkoMode::Datagram, _) | (_, SocketAddr::V6(_)) = (self.mode, self.source_socket_address) {
+            return Err(EkkoError::SocketSetHeaderIncluded({
+                "only raw IPv4 sockets take headers".to_owned()
+            }))
+        }
+
+        let value: libc::c_int = 1;
+        let result = unsafe {
+            libc::setsockopt(self.socket.as_raw_fd(), libc::SOL_IP, libc::IP_HDRINCL, {
+                &(value) as *const libc::c_int as *const libc::c_void
+            }, std::mem::size_of::<libc::c_int>() as libc::socklen_t)
+        };
+
+        if result < 0 {
+            return Err(EkkoError::SocketSetHeaderIncluded({
+                Error::last_os_error().to_string()
+            }))
+        }
+
+        self.included.store(true, Ordering::Relaxed);
+
+        Ok(())
+    }
+
+    #[cfg(not(any(target_os = "linux", target_os = "android")))]
+    pub(crate) fn set_header_included(&self) -> Result<(), EkkoError> {
+        Err(EkkoError::SocketSetHeaderIncluded({
+            "unsupported platform".to_owned()
+        }))
+    }
+
+    fn send_to(&self, request: &[u8], target: SocketAddr) -> Result<usize, Error> {
+        match self.socket.send_to(request, &(target.into())) {
+
+            // Sockets with an error queue report errors for earlier requests
+            // on the next send, these are already queued so the send is retried.
+            Err(_) if self.is_error_queued() => self.socket.send_to(request, {
+                &(target.into())
+            }),
+
+            result => result,
+        }
+    }
+
+    /// Block until the socket, or its error queue, has something to read or
+    /// the timeout passes.
+    #[cfg(unix)]
+    pub(crate) fn wait(&self, timeout: Duration) {
+        use std::os::unix::io::{AsRawFd};
+
+        let mut descriptor = libc::pollfd {
+            fd: self.socket.as_raw_fd(),
+            events: libc::POLLIN,
+            revents: 0,
+        };
+
+        // Rounded up, so waiting until a deadline never returns short of it
+        // only to wait again for nothing.
+        let timeout = timeout.as_nanos().div_ceil(1_000_000)
+            .min(libc::c_int::MAX as u128) as libc::c_int;
+
+        unsafe {
+            libc::poll(&mut descriptor, 1, timeout);
+        }
+    }
+
+    #[cfg(not(unix))]
+    pub(crate) fn wait(&self, _: Duration) {
+        std::thread::sleep(Duration::from_millis(1));
+    }
+
+    /// Stop receiving for good, wakes whoever waits on the socket. The
+    /// kernel wakes them even for sockets never connected, though it
+    /// reports those as not connected.
+    pub(crate) fn shutdown(&self) {
+        self.socket.shutdown(std::net::Shutdown::Read).ok();
+    }
+
+    pub(crate) fn recv<'a>(&self, buf: &'a mut [u8]) -> Result<Option<EkkoReceived<'a>>, EkkoError> {
+        // The error queue goes first, so send timestamps are known before
+        // the responses to them.
+        if self.is_error_queued() || self.timestamps.load(Ordering::Relaxed) {
+            if let Some(queued) = self.recv_err(buf)? {
+                return Ok(Some(queued.received(buf)))
+            }
+        }
+
+        if let EkkoProbe::Udp(_) = self.probe {
+            let mut discard: [MaybeUninit<u8>; 64] = [MaybeUninit::uninit(); 64];
+
+            // Datagrams from the target carry no ports to match against, only
+            // the error queue is of interest.
+            while self.socket.recv(&mut discard).is_ok() {}
+
+            return Ok(None)
+        }
+
+        let message = match self.recv_msg(buf, false) {
+            Some(message) => message,
+            None => return Ok(None),
+        };
+
+        let responding_address = match (self.source_socket_address, message.address) {
+            (SocketAddr::V4(_), Some(SocketAddr::V4(responder))) => SocketAddr::V4(SocketAddrV4::new(*responder.ip(), 0)),
+            (SocketAddr::V6(_), Some(SocketAddr::V6(responder))) => SocketAddr::V6(SocketAddrV6::new(*responder.ip(), 0, 0, {
+                responder.scope_id()
+            })),
+            (SocketAddr::V4(_), _) => return Err(EkkoError::SocketReceiveNoIpv4),
+            (SocketAddr::V6(_), _) => return Err(EkkoError::SocketReceiveNoIpv6),
+        };
+
+        let (length, timestamp, ttl) = (message.length.min(buf.len()), message.timestamp, message.ttl);
+
+        match (responding_address, self.mode) {
+
+            (SocketAddr::V4(_), EkkoMode::Raw) => Ok(Some(EkkoReceived::Response(responding_address, {
+                let header_octets = ((buf.first().ok_or_else(|| {
+                    EkkoError::ResponseReadField("internet protocol header size", {
+                        "empty response".to_owned()
+                    })
+                })? & 0x0F) * 4) as usize;
+
+                match self.probe {
+                    EkkoProbe::Tcp(_) => EkkoPacket::Tcp(&(buf[header_octets.min(length)..length])),
+                    _ => EkkoPacket::V4(&(buf[header_octets.min(length)..length])),
+                }
+            }, timestamp, buf[..length].get(8).copied().or(ttl), false))),
+
+            (SocketAddr::V4(_), EkkoMode::Datagram) => Ok(Some(EkkoReceived::Response(responding_address, {
+                EkkoPacket::V4(&(buf[..length]))
+            }, timestamp, ttl, false))),
+
+            (SocketAddr::V6(_), _) => Ok(Some(EkkoReceived::Response(responding_address, {
+                match self.probe {
+                    EkkoProbe::Tcp(_) => EkkoPacket::Tcp(&(buf[..length])),
+                    _ => EkkoPacket::V6(&(buf[..length])),
+                }
+            }, timestamp, ttl, false))),
+        }
+    }
+
+    /// Read a message from the socket, or from its error queue, along with
+    /// timestamps and extended errors passed as ancillary data.
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    fn recv_msg(&self, buf: &mut [u8], errors: bool) -> Option<EkkoMessage> {
+        use std::os::unix::io::{AsRawFd};
+        use std::convert::{TryFrom};
+
+        fn socket_address(address: &libc::sockaddr_storage) -> Option<SocketAddr> {
+            match address.ss_family as libc::c_int {
+
+                libc::AF_INET => {
+                    let address = unsafe {
+                        std::ptr::read_unaligned(address as *const libc::sockaddr_storage as *const libc::sockaddr_in)
+                    };
+
+                    Some(SocketAddr::from((u32::from_be(address.sin_addr.s_addr).to_be_bytes(), {
+                        u16::from_be(address.sin_port)
+                    })))
+                },
+
+                libc::AF_INET6 => {
+                    let address = unsafe {
+                        std::ptr::read_unaligned(address as *const libc::sockaddr_storage as *const libc::sockaddr_in6)
+                    };
+
+                    Some(SocketAddr::V6(SocketAddrV6::new(address.sin6_addr.s6_addr.into(), {
+                        u16::from_be(address.sin6_port)
+                    }, 0, address.sin6_scope_id)))
+                },
+
+                _ => None,
+            }
+        }
+
+        let mut control: [u64; 64] = [0; 64];
+
+        let mut iov = libc::iovec {
+            iov_base: buf.as_mut_ptr() as *mut libc::c_void,
+            iov_len: buf.len(),
+        };
+
+        let mut address: libc::sockaddr_storage = unsafe {
+            std::mem::zeroed()
+        };
+
+        let mut message: libc::msghdr = unsafe {
+            std::mem::zeroed()
+        };
+
+        message.msg_name = &mut address as *mut libc::sockaddr_storage as *mut libc::c_void;
+        message.msg_namelen = std::mem::size_of_val(&(address)) as libc::socklen_t;
+        message.msg_iov = &mut iov;
+        message.msg_iovlen = 1;
+        message.msg_control = control.as_mut_ptr() as *mut libc::c_void;
+        message.msg_controllen = std::mem::size_of_val(&(control)) as _;
+
+        let flags = match errors {
+            true => libc::MSG_ERRQUEUE | libc::MSG_DONTWAIT,
+            false => libc::MSG_DONTWAIT,
+        };
+
+        let length = unsafe {
+            libc::recvmsg(self.socket.as_raw_fd(), &mut message, flags)
+        };
+
+        if length < 0 {
+            return None
+        }
+
+        let mut received = EkkoMessage {
+
+            length: length as usize,
+            address: socket_address(&(address)),
+
+            timestamp: None,
+            error: None,
+            offender: None,
+            ttl: None,
+        };
+
+        let mut cmsg = unsafe {
+            libc::CMSG_FIRSTHDR(&message)
+        };
+
+        while !cmsg.is_null() {
+            let (level, kind) = unsafe {
+                ((*cmsg).cmsg_level, (*cmsg).cmsg_type)
+            };
+
+            match (level, kind) {
+
+                // Software timestamp comes first, followed by two unused.
+                (libc::SOL_SOCKET, libc::SCM_TIMESTAMPING) => {
+                    let timestamp = unsafe {
+                        std::ptr::read_unaligned(libc::CMSG_DATA(cmsg) as *const libc::timespec)
+                    };
+
+                    if timestamp.tv_sec > 0 || timestamp.tv_nsec > 0 {
+                        received.timestamp = Some(Duration::new(timestamp.tv_sec as u64, {
+                            timestamp.tv_nsec as u32
+                        }));
+                    }
+                },
+
+                (libc::SOL_IP, libc::IP_RECVERR) | (libc::SOL_IPV6, libc::IPV6_RECVERR) => {
+                    let error = unsafe {
+                        std::ptr::read_unaligned(libc::CMSG_DATA(cmsg) as *const libc::sock_extended_err)
+                    };
+
+                    let offender = unsafe {
+                        (libc::CMSG_DATA(cmsg) as *const libc::sock_extended_err).add(1) as *const u8
+                    };
+
+                    received.offender = match error.ee_origin {
+
+                        libc::SO_EE_ORIGIN_ICMP => {
+                            let address = unsafe {
+                                std::ptr::read_unaligned(offender as *const libc::sockaddr_in)
+                            };
+
+                            Some(SocketAddr::V4(SocketAddrV4::new(u32::from_be(address.sin_addr.s_addr).into(), 0)))
+                        },
+
+                        libc::SO_EE_ORIGIN_ICMP6 => {
+                            let address = unsafe {
+                                std::ptr::read_unaligned(offender as *const libc::sockaddr_in6)
+                            };
+
+                            Some(SocketAddr::V6(SocketAddrV6::new(address.sin6_addr.s6_addr.into(), 0, 0, {
+                                address.sin6_scope_id
+                            })))
+                        },
+
+                        _ => None,
+                    };
+
+                    received.error = Some((error.ee_origin, error.ee_type, error.ee_code, error.ee_info, error.ee_data));
+                },
+
+                (libc::SOL_IP, libc::IP_TTL) | (libc::SOL_IPV6, libc::IPV6_HOPLIMIT) => {
+                    let ttl = unsafe {
+                        std::ptr::read_unaligned(libc::CMSG_DATA(cmsg) as *const libc::c_int)
+                    };
+
+                    received.ttl = u8::try_from(ttl).ok();
+                },
+
+                _ => (),
+            }
+
+            cmsg = unsafe {
+                libc::CMSG_NXTHDR(&message, cmsg)
+            };
+        }
+
+        Some(received)
+    }
+
+    #[cfg(not(any(target_os = "linux", target_os = "android")))]
+    fn recv_msg(&self, buf: &mut [u8], errors: bool) -> Option<EkkoMessage> {
+        if errors {
+            return None
+        }
+
+        let (length, responder) = self.socket.recv_from(unsafe {
+            std::mem::transmute::<&mut [u8], &mut [MaybeUninit<u8>]>(&mut buf[..])
+        }).ok()?;
+
+        Some(EkkoMessage {
+
+            length,
+            address: responder.as_socket(),
+
+            timestamp: None,
+            error: None,
+            offender: None,
+            ttl: None,
+        })
+    }
+
+    /// Datagram sockets never see the error messages themselves, instead the
+    /// kernel queues the quoted request along with the error type, code,
+    /// offender and original destination. The error message is rebuilt here
+    /// so it parses the same as one received on a raw socket. Send timestamps
+    /// are queued here as well.
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    fn recv_err(&self, buf: &mut [u8]) -> Result<Option<EkkoQueued>, EkkoError> {
+
+        // The quoted request is read in place, room is left in front for the
+        // rebuilt error message and the largest internet protocol header. The
+        // kernel only queues the payload of datagrams, so their header needs
+        // room as well.
+        let quote = 8 + 40;
+        let offset = match self.probe {
+            EkkoProbe::Udp(_) => 8,
+            EkkoProbe::Icmp | EkkoProbe::Tcp(_) => 0,
+        };
+
+        if buf.len() < quote + offset {
+            return Err(EkkoError::ResponseReadField("originator", {
+                format!("quoted request does not fit in {} octets", buf.len())
+            }))
+        }
+
+        let message = match self.recv_msg(&mut buf[(quote + offset)..], true) {
+            Some(message) => message,
+            None => return Ok(None),
+        };
+
+        let (origin, kind, code, info, data) = match message.error {
+            Some(error) => error,
+            None => return Ok(None),
+        };
+
+        if origin == libc::SO_EE_ORIGIN_TIMESTAMPING {
+            return Ok(message.timestamp.map(|timestamp| {
+                EkkoQueued::Transmitted(data, timestamp)
+            }))
+        }
+
+        let responder = match message.offender {
+            Some(responder) => responder,
+            None => return Ok(None),
+        };
+
+        let quoted_octets = offset + message.length;
+
+        if let EkkoProbe::Udp(_) = self.probe {
+            let source_port = self.socket.local_addr().ok()
+                .and_then(|address| address.as_socket())
+                .map(|address| address.port())
+                .unwrap_or(0);
+
+            let destination_port = message.address.map(|address| address.port())
+                .unwrap_or(0);
+
+            buf[quote..(quote + 2)].copy_from_slice(&(source_port.to_be_bytes()));
+            buf[(quote + 2)..(quote + 4)].copy_from_slice(&(destination_port.to_be_bytes()));
+            buf[(quote + 4)..(quote + 6)].copy_from_slice(&((quoted_octets as u16).to_be_bytes()));
+            buf[(quote + 6)..(quote + 8)].copy_from_slice(&([0, 0]));
+        }
+
+        let header_octets = match responder {
+            SocketAddr::V4(_) => 20,
+            SocketAddr::V6(_) => 40,
+        };
+
+        let start = 8 + header_octets;
+        let length = start + quoted_octets;
+
+        buf.copy_within(quote..(quote + quoted_octets), start);
+        buf[..start].iter_mut().for_each(|octet| *octet = 0);
+
+        buf[0] = kind;
+        buf[1] = code;
+        buf[4..8].copy_from_slice(&(info.to_be_bytes()));
+
+        let quoted_length = (header_octets + quoted_octets) as u16;
+
+        match responder {
+
+            SocketAddr::V4(_) => {
+
+                buf[8] = 0x45;
+                buf[10..12].copy_from_slice(&(quoted_length.to_be_bytes()));
+                buf[17] = self.probe.protocol(self.source_socket_address);
+
+                if let Some(SocketAddr::V4(destination)) = message.address {
+                    buf[24..28].copy_from_slice(&(destination.ip().octets()));
+                }
+            },
+
+            SocketAddr::V6(_) => {
+
+                buf[8] = 0x60;
+                buf[12..14].copy_from_slice(&((quoted_octets as u16).to_be_bytes()));
+                buf[14] = self.probe.protocol(self.source_socket_address);
+
+                if let Some(SocketAddr::V6(destination)) = message.address {
+                    buf[32..48].copy_from_slice(&(destination.ip().octets()));
+                }
+            },
+        }
+
+        Ok(Some(EkkoQueued::Response(responder, length, message.timestamp, message.ttl)))
+    }
+
+    #[cfg(not(any(target_os = "linux", target_os = "android")))]
+    fn recv_err(&self, _: &mut [u8]) -> Result<Option<EkkoQueued>, EkkoError> {
+        Ok(None)
+    }
+
+    /// Have the kernel timestamp requests as they leave and responses as
+    /// they arrive, read along with them from the socket and error queue.
+    /// Enabled before the first send, so the kernel numbers every send.
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    fn enable_timestamps(&self) -> Result<(), EkkoError> {
+        use std::os::unix::io::{AsRawFd};
+
+        let flags: libc::c_uint = libc::SOF_TIMESTAMPING_TX_SOFTWARE |
+            libc::SOF_TIMESTAMPING_RX_SOFTWARE |
+            libc::SOF_TIMESTAMPING_SOFTWARE |
+            libc::SOF_TIMESTAMPING_OPT_TSONLY |
+            libc::SOF_TIMESTAMPING_OPT_ID;
+
+        let result = unsafe {
+            libc::setsockopt(self.socket.as_raw_fd(), libc::SOL_SOCKET, libc::SO_TIMESTAMPING, {
+                &(flags) as *const libc::c_uint as *const libc::c_void
+            }, std::mem::size_of::<libc::c_uint>() as libc::socklen_t)
+        };
+
+        if result < 0 {
+            return Err(EkkoError::SocketSetTimestamps({
+                Error::last_os_error().to_string()
+            }))
+        }
+
+        self.timestamps.store(true, Ordering::Relaxed);
+
+        Ok(())
+    }
+
+    #[cfg(not(any(target_os = "linux", target_os = "android")))]
+    fn enable_timestamps(&self) -> Result<(), EkkoError> {
+        Err(EkkoError::SocketSetTimestamps({
+            "unsupported platform".to_owned()
+        }))
+    }
+
+    /// Whether requests and responses are timestamped by the kernel.
+    pub(crate) fn timestamps(&self) -> bool {
+        self.timestamps.load(Ordering::Relaxed)
+    }
+
+    /// Have the kernel drop everything but echo replies carrying identifier
+    /// and error messages quoting requests carrying it, by types first and
+    /// then by a socket filter. Only raw sockets for echo requests receive
+    /// packets meant for others, so other sockets are left as they are.
+    /// Returns whether the filter was set.
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    pub(crate) fn set_filter(&self, identifier: u16) -> Result<bool, EkkoError> {
+        use std::os::unix::io::{AsRawFd};
+
+        use libc::{
+
+            BPF_LDX,
+            BPF_MSH,
+            BPF_LD,
+            BPF_ABS,
+            BPF_IND,
+            BPF_ALU,
+            BPF_AND,
+            BPF_LSH,
+            BPF_ADD,
+            BPF_JMP,
+            BPF_JEQ,
+            BPF_JA,
+            BPF_RET,
+            BPF_MISC,
+            BPF_TAX,
+
+            BPF_B,
+            BPF_H,
+            BPF_K,
+            BPF_X,
+
+            sock_filter,
+        };
+
+        if self.mode != EkkoMode::Raw || self.probe != EkkoProbe::Icmp {
+            return Ok(false)
+        }
+
+        fn stmt(code: u32, k: u32) -> sock_filter {
+            sock_filter { code: code as u16, jt: 0, jf: 0, k }
+        }
+
+        fn jump(code: u32, k: u32, jt: u8, jf: u8) -> sock_filter {
+            sock_filter { code: code as u16, jt, jf, k }
+        }
+
+        let identifier = identifier as u32;
+        let (accept, drop) = (u32::MAX, 0);
+
+        // Offsets of jumps count from the next statement.
+        let (level, name, filter, types): (libc::c_int, libc::c_int, Vec<sock_filter>, Vec<u8>) = match self.source_socket_address {
+
+            // Packets start with the internet protocol header.
+            SocketAddr::V4(_) => (libc::SOL_RAW, 1, vec![
+                stmt(BPF_LDX | BPF_B | BPF_MSH, 0),
+                stmt(BPF_LD | BPF_B | BPF_IND, 0),
+                jump(BPF_JMP | BPF_JEQ | BPF_K, 0, 5, 0),
+                jump(BPF_JMP | BPF_JEQ | BPF_K, 3, 6, 0),
+                jump(BPF_JMP | BPF_JEQ | BPF_K, 4, 5, 0),
+                jump(BPF_JMP | BPF_JEQ | BPF_K, 5, 4, 0),
+                jump(BPF_JMP | BPF_JEQ | BPF_K, 11, 3, 0),
+                jump(BPF_JMP | BPF_JEQ | BPF_K, 12, 2, 10),
+                // Echo reply.
+                stmt(BPF_LD | BPF_H | BPF_IND, 4),
+                stmt(BPF_JMP | BPF_JA, 6),
+                // Error message, skip the quoted internet protocol header.
+                stmt(BPF_LD | BPF_B | BPF_IND, 8),
+                stmt(BPF_ALU | BPF_AND | BPF_K, 0x0F),
+                stmt(BPF_ALU | BPF_LSH | BPF_K, 2),
+                stmt(BPF_ALU | BPF_ADD | BPF_X, 0),
+                stmt(BPF_MISC | BPF_TAX, 0),
+                stmt(BPF_LD | BPF_H | BPF_IND, 12),
+                jump(BPF_JMP | BPF_JEQ | BPF_K, identifier, 0, 1),
+                stmt(BPF_RET | BPF_K, accept),
+                stmt(BPF_RET | BPF_K, drop),
+            ], vec![0, 3, 4, 5, 11, 12]),
+
+            // Packets start with the message, redirects quote no identifier
+            // where it can be found and are let through.
+            SocketAddr::V6(_) => (libc::IPPROTO_ICMPV6, 1, vec![
+                stmt(BPF_LD | BPF_B | BPF_ABS, 0),
+                jump(BPF_JMP | BPF_JEQ | BPF_K, 129, 4, 0),
+                jump(BPF_JMP | BPF_JEQ | BPF_K, 1, 5, 0),
+                jump(BPF_JMP | BPF_JEQ | BPF_K, 2, 4, 0),
+                jump(BPF_JMP | BPF_JEQ | BPF_K, 3, 3, 0),
+                jump(BPF_JMP | BPF_JEQ | BPF_K, 4, 2, 8),
+                // Echo reply.
+                stmt(BPF_LD | BPF_H | BPF_ABS, 4),
+                stmt(BPF_JMP | BPF_JA, 5),
+                // Error message, the quoted request may carry a fragment
+                // header after the fixed internet protocol header.
+                stmt(BPF_LD | BPF_B | BPF_ABS, 14),
+                jump(BPF_JMP | BPF_JEQ | BPF_K, 44, 2, 0),
+                stmt(BPF_LD | BPF_H | BPF_ABS, 52),
+                stmt(BPF_JMP | BPF_JA, 1),
+                stmt(BPF_LD | BPF_H | BPF_ABS, 60),
+                jump(BPF_JMP | BPF_JEQ | BPF_K, identifier, 0, 1),
+                stmt(BPF_RET | BPF_K, accept),
+                stmt(BPF_RET | BPF_K, drop),
+            ], vec![1, 2, 3, 4, 129, 137]),
+        };
+
+        // Set bits block types, those of interest are cleared.
+        let mut blocked: [u32; 8] = [u32::MAX; 8];
+        for kind in types {
+            blocked[(kind >> 5) as usize] &= !(1 << (kind & 31));
+        }
+
+        let size = match self.source_socket_address {
+            SocketAddr::V4(_) => std::mem::size_of::<u32>(),
+            SocketAddr::V6(_) => std::mem::size_of_val(&(blocked)),
+        };
+
+        let result = unsafe {
+            libc::setsockopt(self.socket.as_raw_fd(), level, name, {
+                blocked.as_ptr() as *const libc::c_void
+            }, size as libc::socklen_t)
+        };
+
+        if result < 0 {
+            return Err(EkkoError::SocketSetFilter({
+                Error::last_os_error().to_string()
+            }))
+        }
+
+        self.socket.attach_filter(&(filter)).map_err(|e| {
+            EkkoError::SocketSetFilter(e.to_string())
+        })?;
+
+        Ok(true)
+    }
+
+    #[cfg(not(any(target_os = "linux", target_os = "android")))]
+    pub(crate) fn set_filter(&self, _: u16) -> Result<bool, EkkoError> {
+        Err(EkkoError::SocketSetFilter({
+            "unsupported platform".to_owned()
+        }))
+    }
+}
+
+#[cfg(unix)]
+impl std::os::unix::io::AsRawFd for EkkoSocket {
+    fn as_raw_fd(&self) -> std::os::unix::io::RawFd {
+        self.socket.as_raw_fd()
+    }
+}
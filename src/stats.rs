@@ -0,0 +1,284 @@
+use std::{
+
+    collections::{
+
+        BTreeMap,
+        VecDeque,
+    },
+
+    time::{Duration},
+};
+
+use super::{
+
+    responses::{EkkoResponse},
+};
+
+/// Summary of responses, the same `ping` prints when done. Responses from the
+/// destination, or from hops with exceeded time to live, count as received,
+/// other error messages count as errors. Round trip times are those seen by
+/// the kernel when timestamps are enabled, otherwise those seen from
+/// userspace. Percentiles are taken over the most recent round trip times
+/// only, so long running sessions are summarised in constant memory.
+///
+/// ## Example
+/// ```rust,no_run
+/// use std::collections::{HashMap};
+///
+/// use ekko::{
+///
+///     EkkoError,
+///     EkkoStats,
+///     MultiEkko,
+/// };
+///
+/// fn main() -> Result<(), EkkoError> {
+///     let sender = MultiEkko::with_targets(vec![[8, 8, 8, 8], [1, 1, 1, 1]])?;
+///     let mut stats = HashMap::new();
+///
+///     for _ in 0..10 {
+///         for (target, response) in sender.targets().into_iter().zip(sender.send(64)?) {
+///             stats.entry(target).or_insert_with(EkkoStats::default).push(&(response));
+///         }
+///     }
+///
+///     for (target, stats) in stats.iter() {
+///         println!("{} {:.1}% loss, avg {:?}", target, stats.loss(), stats.avg());
+///     }
+///
+///     Ok(())
+/// }
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct EkkoStats {
+
+    transmitted: usize,
+    duplicates: usize,
+    received: usize,
+    errors: usize,
+
+    min: Option<Duration>,
+    max: Option<Duration>,
+    /// Sum of round trip times and of their squares, in seconds.
+    sum: f64,
+    squares: f64,
+
+    /// Most recent round trip times in the order they were pushed.
+    samples: VecDeque<Duration>,
+    /// Interarrival jitter in nanoseconds.
+    jitter: f64,
+}
+
+/// Number of round trip times kept for percentiles.
+const SAMPLES: usize = 4096;
+
+impl EkkoStats {
+
+    /// Summaries for each number of hops.
+    pub fn by_hops<'a, I>(responses: I) -> BTreeMap<u32, EkkoStats>
+    where I: IntoIterator<Item = &'a EkkoResponse> {
+        let mut stats: BTreeMap<u32, EkkoStats> = BTreeMap::new();
+
+        for response in responses {
+            stats.entry(response.data().hops)
+                .or_default()
+                .push(response);
+        }
+
+        stats
+    }
+
+    /// Add a response, including lacking ones. Duplicate responses count as
+    /// duplicates only.
+    pub fn push(&mut self, response: &EkkoResponse) {
+        let data = response.data();
+
+        if let EkkoResponse::Duplicate(_) = response {
+            self.duplicates += 1;
+            return
+        }
+
+        self.duplicates += data.duplicates as usize;
+        self.transmitted += 1;
+
+        match response {
+
+            EkkoResponse::Destination(_) | EkkoResponse::Exceeded(_) => {
+                let elapsed = data.kernel_elapsed.unwrap_or(data.elapsed);
+
+                // Interarrival jitter (RFC 3550), difference in transit time
+                // between consecutive responses smoothed by a sixteenth.
+                if let Some(previous) = self.samples.back() {
+                    let difference = (elapsed.as_nanos() as f64) - (previous.as_nanos() as f64);
+                    self.jitter += (difference.abs() - self.jitter) / 16.0;
+                }
+
+                self.min = Some(self.min.map_or(elapsed, |min| min.min(elapsed)));
+                self.max = Some(self.max.map_or(elapsed, |max| max.max(elapsed)));
+
+                self.sum += elapsed.as_secs_f64();
+                self.squares += elapsed.as_secs_f64() * elapsed.as_secs_f64();
+                self.received += 1;
+
+                if self.samples.len() == SAMPLES {
+                    self.samples.pop_front();
+                }
+
+                self.samples.push_back(elapsed);
+            },
+
+            EkkoResponse::Lacking(_) => (),
+
+            _ => self.errors += 1,
+        }
+    }
+
+    /// Number of requests sent.
+    pub fn transmitted(&self) -> usize {
+        self.transmitted
+    }
+
+    /// Number of requests answered.
+    pub fn received(&self) -> usize {
+        self.received
+    }
+
+    /// Number of responses seen more than once.
+    pub fn duplicates(&self) -> usize {
+        self.duplicates
+    }
+
+    /// Number of requests answered with an error message.
+    pub fn errors(&self) -> usize {
+        self.errors
+    }
+
+    /// Percentage of requests not answered.
+    pub fn loss(&self) -> f64 {
+        match self.transmitted {
+            0 => 0.0,
+            transmitted => 100.0 * ((transmitted - self.received()) as f64) / (transmitted as f64),
+        }
+    }
+
+    pub fn min(&self) -> Option<Duration> {
+        self.min
+    }
+
+    pub fn max(&self) -> Option<Duration> {
+        self.max
+    }
+
+    pub fn avg(&self) -> Option<Duration> {
+        match self.received {
+            0 => None,
+            n => Some(Duration::from_secs_f64(self.sum / (n as f64))),
+        }
+    }
+
+    /// Mean deviation as `ping` reports it, the standard deviation of round
+    /// trip times.
+    pub fn mdev(&self) -> Option<Duration> {
+        let n = self.received as f64;
+
+        self.avg().map(|_| {
+            let mean = self.sum / n;
+            Duration::from_secs_f64((self.squares / n - mean * mean).max(0.0).sqrt())
+        })
+    }
+
+    /// Interarrival jitter (RFC 3550) over responses in the order pushed.
+    pub fn jitter(&self) -> Option<Duration> {
+        match self.received {
+            0 | 1 => None,
+            _ => Some(Duration::from_nanos(self.jitter.round() as u64)),
+        }
+    }
+
+    /// Round trip time below which given percentage of recent responses
+    /// fall, by nearest rank.
+    pub fn percentile(&self, percentile: f64) -> Option<Duration> {
+        let mut samples: Vec<Duration> = self.samples.iter().copied().collect();
+        samples.sort_unstable();
+
+        let rank = ((percentile.clamp(0.0, 100.0) / 100.0) * (samples.len() as f64)).ceil() as usize;
+        samples.get(rank.max(1) - 1).copied()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+
+        time::{
+
+            Duration,
+            Instant,
+        },
+
+        net::{IpAddr},
+    };
+
+    use super::{EkkoStats};
+    use crate::{
+
+        EkkoResponse,
+        EkkoData,
+    };
+
+    fn data(sequence: u16, elapsed: u64) -> EkkoData {
+        EkkoData {
+
+            address: Some(IpAddr::from([10, 0, 0, 1])),
+
+            ..EkkoData::new((Instant::now(), Duration::from_millis(elapsed)), (0xBAD, sequence), 64)
+        }
+    }
+
+    #[test]
+    fn summary() {
+        let mut stats = EkkoStats::default();
+
+        for response in [
+            EkkoResponse::Destination(data(0, 10)),
+            EkkoResponse::Destination(data(1, 30)),
+            EkkoResponse::Lacking(data(2, 1000)),
+            EkkoResponse::Duplicate(data(1, 30)),
+            EkkoResponse::Destination(data(3, 20)),
+        ].iter() {
+            stats.push(response);
+        }
+
+        assert_eq!(stats.transmitted(), 4);
+        assert_eq!(stats.received(), 3);
+        assert_eq!(stats.duplicates(), 1);
+        assert_eq!(stats.loss(), 25.0);
+
+        assert_eq!(stats.min(), Some(Duration::from_millis(10)));
+        assert_eq!(stats.max(), Some(Duration::from_millis(30)));
+        assert_eq!(stats.avg(), Some(Duration::from_millis(20)));
+        assert_eq!(stats.percentile(50.0), Some(Duration::from_millis(20)));
+        assert_eq!(stats.percentile(100.0), Some(Duration::from_millis(30)));
+
+        // Transit differences of 20 and 10 milliseconds.
+        let jitter = (20_000_000.0 / 16.0) + (10_000_000.0 - 20_000_000.0 / 16.0) / 16.0;
+        assert_eq!(stats.jitter(), Some(Duration::from_nanos(jitter as u64)));
+
+        let mdev = stats.mdev().unwrap().as_secs_f64();
+        assert!((mdev - (200.0_f64 / 3.0).sqrt() / 1000.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn wrapped() {
+        let mut stats = EkkoStats::default();
+
+        // Sequences wrap around in long sessions, replies are still received.
+        for _ in 0..2 {
+            stats.push(&(EkkoResponse::Destination(data(0, 10))));
+        }
+
+        assert_eq!(stats.transmitted(), 2);
+        assert_eq!(stats.received(), 2);
+        assert_eq!(stats.duplicates(), 0);
+    }
+}
@@ -0,0 +1,55 @@
+use std::{
+
+    time::{Duration},
+};
+
+use super::{
+
+    requests::{EkkoRequests},
+    error::{EkkoError},
+
+    responses::{EkkoResponse},
+};
+
+/// Responses to requests already sent, yielded as soon as they are matched.
+/// Requests going unanswered for the timeout are yielded as lacking, ends
+/// once every request was yielded. Take a look at
+/// [`Ekko::stream_range`](crate::Ekko::stream_range).
+pub struct EkkoStream {
+
+    requests: EkkoRequests,
+    timeout: Duration,
+}
+
+impl EkkoStream {
+
+    pub(crate) fn new(requests: EkkoRequests, timeout: Duration) -> EkkoStream {
+        EkkoStream {
+
+            requests,
+            timeout,
+        }
+    }
+
+    fn inner_next(&mut self) -> Result<Option<EkkoResponse>, EkkoError> {
+        loop {
+
+            if let Some(response) = self.requests.next(self.timeout) {
+                return Ok(Some(response))
+            }
+
+            match self.requests.deadline(self.timeout) {
+                Some(deadline) => self.requests.wait(deadline)?,
+                None => return Ok(None),
+            }
+        }
+    }
+}
+
+impl Iterator for EkkoStream {
+    type Item = Result<EkkoResponse, EkkoError>;
+
+    fn next(&mut self) -> Option<Result<EkkoResponse, EkkoError>> {
+        self.inner_next().transpose()
+    }
+}
@@ -0,0 +1,663 @@
+use std::{
+
+    time::{
+
+        Duration,
+        Instant,
+    },
+
+    net::{IpAddr},
+};
+
+use super::{
+
+    packets::{PAYLOAD},
+    stats::{EkkoStats},
+    error::{EkkoError},
+    socket::{EkkoProbe},
+    sender::{
+
+        EkkoEcn,
+        Ekko,
+    },
+
+    responses::{
+
+        EkkoResponse,
+        EkkoHeader,
+    },
+};
+
+/// Field of a request changed on its way to a hop, as told by the header
+/// quoted back by it. Contains the quoted value.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TraceModification {
+    /// Rewritten by a NAT.
+    Source(IpAddr),
+    Destination(IpAddr),
+    /// Left above one on running out of hops, reset or not decremented.
+    Ttl(u8),
+    /// Type of service (IPv4) or traffic class (IPv6).
+    Tos(u8),
+    /// Compared for echo requests on raw IPv4 sockets only, the kernel picks
+    /// it for others.
+    Identification(u16),
+    Length(u16),
+    /// Checksum of the echo request, datagram or segment.
+    Checksum(u16),
+}
+
+/// Responses for a single number of hops.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TraceHop {
+    /// Number of hops.
+    pub hops: u32,
+    /// Responses in the order requests were sent, one for each query.
+    pub responses: Vec<EkkoResponse>,
+    /// Headers of requests as sent, in the same order as responses.
+    pub sent: Vec<EkkoHeader>,
+}
+
+impl TraceHop {
+
+    /// Distinct responders in order of appearance, along with the elapsed
+    /// time of each of their responses.
+    pub fn responders(&self) -> Vec<(IpAddr, Vec<Duration>)> {
+        let mut responders: Vec<(IpAddr, Vec<Duration>)> = Vec::new();
+
+        for data in self.responses.iter().map(EkkoResponse::data) {
+            if let Some(address) = data.address {
+                match responders.iter_mut().find(|(responder, _)| responder == &(address)) {
+                    Some((_, elapsed)) => elapsed.push(data.elapsed),
+                    None => responders.push((address, vec![data.elapsed])),
+                }
+            }
+        }
+
+        responders
+    }
+
+    /// Distinct types of service (IPv4) or traffic classes (IPv6) quoted back
+    /// by responders in order of appearance, differing from what was sent
+    /// when a router before this hop rewrote it.
+    pub fn quoted_tos(&self) -> Vec<u8> {
+        let mut quoted: Vec<u8> = Vec::new();
+
+        for tos in self.responses.iter().filter_map(|response| response.data().quoted.map(|quoted| quoted.tos)) {
+            if !(quoted.contains(&(tos))) {
+                quoted.push(tos);
+            }
+        }
+
+        quoted
+    }
+
+    /// Distinct ECN codepoints quoted back by responders in order of
+    /// appearance, see [`TraceHop::quoted_tos`].
+    pub fn ecn(&self) -> Vec<EkkoEcn> {
+        let mut quoted: Vec<EkkoEcn> = Vec::new();
+
+        for ecn in self.quoted_tos().into_iter().map(EkkoEcn::from_tos) {
+            if !(quoted.contains(&(ecn))) {
+                quoted.push(ecn);
+            }
+        }
+
+        quoted
+    }
+
+    /// Fields of requests changed on their way to this hop, as told by the
+    /// headers quoted back, distinct and in order of appearance. Changes show
+    /// at every hop past where they were made, so the first hop reporting a
+    /// change tells where. Only raw sockets see the quoted header.
+    pub fn modifications(&self) -> Vec<TraceModification> {
+        let mut modifications: Vec<TraceModification> = Vec::new();
+
+        for (sent, response) in self.sent.iter().zip(self.responses.iter()) {
+            let quoted = match response.data().quoted {
+                Some(quoted) => quoted,
+                None => continue,
+            };
+
+            // The router a request runs out of hops at quotes a time to live
+            // of one, or zero when decremented first.
+            let ttl = match response {
+                EkkoResponse::Exceeded(_) => quoted.ttl > 1,
+                _ => false,
+            };
+
+            let identification = match (sent.identification, quoted.identification) {
+                (Some(sent), Some(quoted)) if sent != quoted => Some(TraceModification::Identification(quoted)),
+                _ => None,
+            };
+
+            // Requests without a route to their target when the sender was
+            // built leave the source to the kernel.
+            let source = !(sent.source.is_unspecified()) && quoted.source != sent.source;
+
+            let checksum = match (sent.checksum, quoted.checksum) {
+                (Some(sent), Some(quoted)) if sent != quoted => Some(TraceModification::Checksum(quoted)),
+                _ => None,
+            };
+
+            let found = [
+                source.then_some(TraceModification::Source(quoted.source)),
+                (quoted.destination != sent.destination).then_some(TraceModification::Destination(quoted.destination)),
+                ttl.then_some(TraceModification::Ttl(quoted.ttl)),
+                (quoted.tos != sent.tos).then_some(TraceModification::Tos(quoted.tos)),
+                identification,
+                (quoted.length != sent.length).then_some(TraceModification::Length(quoted.length)),
+                checksum,
+            ];
+
+            for modification in found.iter().flatten() {
+                if !(modifications.contains(modification)) {
+                    modifications.push(*modification);
+                }
+            }
+        }
+
+        modifications
+    }
+
+    /// Summary of responses for this number of hops.
+    pub fn stats(&self) -> EkkoStats {
+        let mut stats = EkkoStats::default();
+
+        for response in self.responses.iter() {
+            stats.push(response);
+        }
+
+        stats
+    }
+
+    /// Whether the destination or an unreachable response was seen.
+    pub fn is_final(&self) -> bool {
+        self.responses.iter().any(|response| {
+            matches!(response, EkkoResponse::Unreachable(_) | EkkoResponse::Destination(_))
+        })
+    }
+}
+
+/// What became of the ECN codepoint of requests on their way to a hop, as
+/// quoted back by it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TraceEcn {
+    /// Arrived as sent.
+    Kept,
+    /// Cleared to not ECN-capable (bleached) before the hop.
+    Cleared,
+    /// Changed to another codepoint before the hop, congested queues marking
+    /// congestion experienced included.
+    Rewritten(EkkoEcn),
+}
+
+/// Result of a traceroute.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Trace {
+    /// Probed hops in order, ends at the first hop that reached the
+    /// destination or responded with unreachable.
+    pub hops: Vec<TraceHop>,
+    /// Type of service (IPv4) or traffic class (IPv6) of all requests.
+    pub tos: u8,
+}
+
+impl Trace {
+
+    /// For each hop, what became of the ECN codepoint sent as quoted back by
+    /// responders. The first hop other than kept tells where marks get
+    /// cleared or rewritten, hops quoting nothing are left empty. Only raw
+    /// sockets see the quoted header.
+    pub fn ecn(&self) -> Vec<(u32, Vec<TraceEcn>)> {
+        let sent = EkkoEcn::from_tos(self.tos);
+
+        self.hops.iter().map(|hop| {
+            let changes = hop.ecn().into_iter().map(|ecn| match ecn {
+                ecn if ecn == sent => TraceEcn::Kept,
+                EkkoEcn::NotEct => TraceEcn::Cleared,
+                ecn => TraceEcn::Rewritten(ecn),
+            }).collect();
+
+            (hop.hops, changes)
+        }).collect()
+    }
+
+    /// Whether the trace reached the destination.
+    pub fn is_complete(&self) -> bool {
+        self.hops.last().map(|hop| {
+            hop.responses.iter().any(|response| {
+                matches!(response, EkkoResponse::Destination(_))
+            })
+        }).unwrap_or(false)
+    }
+}
+
+/// Traceroute builder, take a look at the default implementation.
+///
+/// ## Example
+/// ```rust,no_run
+/// use ekko::{
+///
+///     Traceroute,
+///     EkkoError,
+///     Ekko,
+/// };
+///
+/// fn main() -> Result<(), EkkoError> {
+///     let sender = Ekko::with_target([8, 8, 8, 8])?;
+///     let trace = Traceroute::default()
+///         .max_hops(32)
+///         .queries(3)
+///         .trace(&sender)?;
+///
+///     for hop in trace.hops.iter() {
+///         println!("{} {:?}", hop.hops, hop.responders())
+///     }
+///
+///     Ok(())
+/// }
+/// ```
+#[derive(Clone, Debug)]
+pub struct Traceroute {
+
+    first_hop: u32,
+    max_hops: u32,
+
+    queries: usize,
+    window: u32,
+
+    paris: bool,
+    tos: u8,
+
+    timeout: Duration,
+}
+
+impl Default for Traceroute {
+    fn default() -> Traceroute {
+        Traceroute {
+
+            first_hop: 1,
+            max_hops: 30,
+
+            queries: 3,
+            window: 4,
+
+            paris: false,
+            tos: 0,
+
+            timeout: {
+
+                Duration::from_millis(1000)
+            },
+        }
+    }
+}
+
+impl Traceroute {
+
+    /// Number of hops to start from.
+    pub fn first_hop(mut self, hops: u32) -> Traceroute {
+        self.first_hop = hops;
+        self
+    }
+
+    /// Number of hops to give up after.
+    pub fn max_hops(mut self, hops: u32) -> Traceroute {
+        self.max_hops = hops;
+        self
+    }
+
+    /// Number of requests sent for each number of hops.
+    pub fn queries(mut self, queries: usize) -> Traceroute {
+        self.queries = queries.max(1);
+        self
+    }
+
+    /// Number of hops probed concurrently.
+    pub fn window(mut self, window: u32) -> Traceroute {
+        self.window = window.max(1);
+        self
+    }
+
+    /// Keep the checksum of all requests the same (Paris traceroute), so load
+    /// balancers hashing on it send every request along the same path. Not
+    /// supported for datagrams, which are sent to a new port for every
+    /// request.
+    pub fn paris(mut self, paris: bool) -> Traceroute {
+        self.paris = paris;
+        self
+    }
+
+    /// Type of service (IPv4) or traffic class (IPv6) of all requests, see
+    /// [`TraceHop::quoted_tos`] for what routers along the way made of it.
+    pub fn tos(mut self, tos: u8) -> Traceroute {
+        self.tos = tos;
+        self
+    }
+
+    /// ECN codepoint of all requests, replaces that of the type of service.
+    /// See [`Trace::ecn`] for where along the path it was cleared or
+    /// rewritten.
+    pub fn ecn(mut self, ecn: EkkoEcn) -> Traceroute {
+        self.tos = ecn.apply(self.tos);
+        self
+    }
+
+    /// Time to wait for responses to each window.
+    pub fn timeout(mut self, timeout: Duration) -> Traceroute {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Trace the route to the senders target, stops at the first hop that
+    /// reached the destination or responded with unreachable.
+    pub fn trace(&self, sender: &Ekko) -> Result<Trace, EkkoError> {
+        if let (true, EkkoProbe::Udp(_)) = (self.paris, sender.probe()) {
+            return Err(EkkoError::RequestUnsupported("paris tracing"))
+        }
+
+        let identifier: u16 = rand::random();
+        let mut sequence: u16 = 0;
+
+        let flow: Option<u16> = match self.paris {
+            true => Some(rand::random()),
+            false => None,
+        };
+
+        let mut trace = Trace {
+            hops: Vec::new(),
+            tos: self.tos,
+        };
+
+        // Requests go out with headers built here where the socket allows,
+        // so their identification is known.
+        let identified = sender.inner_identified();
+        let sender = identified.as_ref().unwrap_or(sender);
+
+        sender.inner_reserve({
+            (self.window as usize) * self.queries
+        }, PAYLOAD.len())?;
+
+        let mut first = self.first_hop;
+        while first <= self.max_hops {
+
+            let last = first.saturating_add(self.window)
+                .min(self.max_hops.saturating_add(1));
+
+            let mut requests = sender.inner_requests({
+                ((last - first) as usize) * self.queries
+            });
+
+            let mut sent: Vec<EkkoHeader> = Vec::with_capacity({
+                ((last - first) as usize) * self.queries
+            });
+
+            for hop in first..last {
+                for _ in 0..self.queries {
+
+                    let timepoint = Instant::now();
+                    let pkt = sender.inner_send(hop, self.tos, {
+                        (identifier, sequence)
+                    }, flow, PAYLOAD)?;
+
+                    sent.push(EkkoHeader {
+
+                        source: sender.inner_source(),
+                        destination: sender.target(),
+
+                        ttl: hop.min(255) as u8,
+                        tos: self.tos,
+
+                        identification: pkt.identification,
+                        length: pkt.length,
+
+                        checksum: pkt.checksum,
+                    });
+
+                    requests.push(timepoint, sender.target(), pkt, hop);
+                    sequence = sequence.wrapping_add(1);
+                }
+            }
+
+            sender.inner_wait(&mut requests, Instant::now() + self.timeout)?;
+
+            let mut responses = requests.finish().into_iter();
+            let mut sent = sent.into_iter();
+
+            for hop in first..last {
+
+                let hop = TraceHop {
+                    responses: responses.by_ref()
+                        .take(self.queries)
+                        .collect(),
+
+                    sent: sent.by_ref()
+                        .take(self.queries)
+                        .collect(),
+
+                    hops: hop,
+                };
+
+                let is_final = hop.is_final();
+                trace.hops.push(hop);
+
+                if is_final {
+                    return Ok(trace)
+                }
+            }
+
+            first = last;
+        }
+
+        Ok(trace)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+
+        time::{
+
+            Duration,
+            Instant,
+        },
+
+        net::{IpAddr},
+    };
+
+    use super::{
+
+        TraceModification,
+        TraceHop,
+        TraceEcn,
+        Trace,
+    };
+
+    use crate::{
+
+        EkkoResponse,
+        EkkoHeader,
+        EkkoData,
+        EkkoEcn,
+    };
+
+    fn header(ttl: u8, tos: u8) -> EkkoHeader {
+        EkkoHeader {
+
+            source: IpAddr::from([192, 168, 0, 2]),
+            destination: IpAddr::from([8, 8, 8, 8]),
+
+            ttl,
+            tos,
+
+            identification: None,
+            length: 27,
+
+            checksum: Some(0xF352),
+        }
+    }
+
+    fn data(address: Option<IpAddr>, elapsed: u64) -> EkkoData {
+        EkkoData {
+
+            address,
+
+            ..EkkoData::new((Instant::now(), Duration::from_millis(elapsed)), (0xBAD, 0), 4)
+        }
+    }
+
+    #[test]
+    fn responders() {
+        let first = IpAddr::from([10, 0, 0, 1]);
+        let second = IpAddr::from([10, 0, 0, 2]);
+
+        let hop = TraceHop {
+
+            responses: vec![
+                EkkoResponse::Exceeded(data(Some(first), 3)),
+                EkkoResponse::Lacking(data(None, 1000)),
+                EkkoResponse::Exceeded(data(Some(second), 5)),
+                EkkoResponse::Exceeded(data(Some(first), 4)),
+            ],
+
+            sent: Vec::new(),
+            hops: 4,
+        };
+
+        assert_eq!(hop.responders(), vec![
+            (first, vec![Duration::from_millis(3), Duration::from_millis(4)]),
+            (second, vec![Duration::from_millis(5)]),
+        ]);
+
+        assert!(!hop.is_final());
+    }
+
+    #[test]
+    fn ecn() {
+        let hop = |hops: u32, quoted: &[Option<u8>]| TraceHop {
+
+            responses: quoted.iter().map(|tos| EkkoResponse::Exceeded(EkkoData {
+                quoted: tos.map(|tos| header(1, tos)),
+                ..data(Some(IpAddr::from([10, 0, 0, hops as u8])), 1)
+            })).collect(),
+
+            sent: Vec::new(),
+            hops,
+        };
+
+        // Expedited forwarding with ECT(1), bleached past the second hop.
+        let trace = Trace {
+
+            hops: vec![
+                hop(1, &[Some(0xB9), Some(0xB9)]),
+                hop(2, &[Some(0xB9), Some(0xBB)]),
+                hop(3, &[None, None]),
+                hop(4, &[Some(0x00), Some(0xB8)]),
+            ],
+
+            tos: EkkoEcn::Ect1.apply(0xB8),
+        };
+
+        assert_eq!(trace.hops[1].ecn(), vec![EkkoEcn::Ect1, EkkoEcn::Ce]);
+        assert_eq!(trace.ecn(), vec![
+            (1, vec![TraceEcn::Kept]),
+            (2, vec![TraceEcn::Kept, TraceEcn::Rewritten(EkkoEcn::Ce)]),
+            (3, vec![]),
+            (4, vec![TraceEcn::Cleared]),
+        ]);
+    }
+
+    #[test]
+    fn modifications() {
+        let quoted = |quoted: EkkoHeader| EkkoResponse::Exceeded(EkkoData {
+            quoted: Some(quoted),
+            ..data(Some(IpAddr::from([10, 0, 0, 3])), 1)
+        });
+
+        // Source rewritten by a NAT along with the identifier, and so the
+        // checksum, of the echo request.
+        let hop = TraceHop {
+
+            responses: vec![
+                quoted(EkkoHeader {
+                    source: IpAddr::from([203, 0, 113, 1]),
+                    checksum: Some(0xF0F0),
+                    ..header(1, 0)
+                }),
+                EkkoResponse::Lacking(data(None, 1000)),
+                quoted(EkkoHeader {
+                    source: IpAddr::from([203, 0, 113, 1]),
+                    ..header(0, 0)
+                }),
+                quoted(header(9, 0)),
+            ],
+
+            sent: vec![header(3, 0); 4],
+            hops: 3,
+        };
+
+        assert_eq!(hop.modifications(), vec![
+            TraceModification::Source(IpAddr::from([203, 0, 113, 1])),
+            TraceModification::Checksum(0xF0F0),
+            TraceModification::Ttl(9),
+        ]);
+    }
+
+    #[test]
+    fn identification() {
+        let identified = |identification: Option<u16>| EkkoHeader {
+            identification,
+            ..header(1, 0)
+        };
+
+        let quoted = |quoted: EkkoHeader| EkkoResponse::Exceeded(EkkoData {
+            quoted: Some(quoted),
+            ..data(Some(IpAddr::from([10, 0, 0, 1])), 1)
+        });
+
+        // Rewritten for the second request only, the third was sent with an
+        // identification picked by the kernel.
+        let hop = TraceHop {
+
+            responses: vec![
+                quoted(identified(Some(0x1234))),
+                quoted(identified(Some(0x4321))),
+                quoted(identified(Some(0x4321))),
+            ],
+
+            sent: vec![
+                identified(Some(0x1234)),
+                identified(Some(0x1235)),
+                identified(None),
+            ],
+
+            hops: 1,
+        };
+
+        assert_eq!(hop.modifications(), vec![
+            TraceModification::Identification(0x4321),
+        ]);
+    }
+
+    #[test]
+    fn unspecified_source() {
+        let unspecified = EkkoHeader {
+            source: IpAddr::from([0, 0, 0, 0]),
+            ..header(1, 0)
+        };
+
+        let hop = TraceHop {
+
+            responses: vec![EkkoResponse::Exceeded(EkkoData {
+                quoted: Some(header(1, 0)),
+                ..data(Some(IpAddr::from([10, 0, 0, 1])), 1)
+            })],
+
+            sent: vec![unspecified],
+            hops: 1,
+        };
+
+        assert!(hop.modifications().is_empty());
+    }
+}